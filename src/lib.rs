@@ -36,6 +36,7 @@
 //! #     )
 //! # }
 //! #
+//! # #[cfg(feature = "json")]
 //! # #[actix_web::test]
 //! # async fn test_builder_against_handler() {
 //! #     let app = test::init_service(
@@ -85,30 +86,312 @@
 //!
 //! ## Features
 //!
-//! - `json`: Enables the `with_json` method to add `application/json` parts from serializable data.
+//! - `json`: Enables the `with_json` method to add `application/json` parts from serializable data,
+//!   and the `with_manifest_field` method describing all other parts.
+//! - `base64`: Enables the `with_base64_content` method to add binary parts from base64 strings.
+//! - `mime`: Enables the `validate_content_types` method to catch malformed content types.
+//! - `sha2`: Enables the `with_aggregate_checksum_field` method to add a SHA-256 digest field,
+//!   and the `build_with_digest` method returning the SHA-256 of the body.
+//! - `mmap`: Enables the `with_mmap_file` method to add large files without reading them into memory.
+//! - `futures`: Enables the `with_async_read` method to add a file part from an `AsyncRead` source.
+//! - `unicode`: Enables the `with_filename_normalized` method to emit NFC or NFD filenames.
+//! - `async-std`: Enables the `into_async_std_body` method returning a framework-agnostic payload.
 //!
-use actix_web::http::header::{HeaderName, HeaderValue, CONTENT_TYPE};
+use std::collections::HashMap;
+#[cfg(feature = "mmap")]
+use std::fs::File;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{fs, io, path::Path};
+
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::header::{
+    AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, HeaderName, HeaderValue, TRANSFER_ENCODING,
+};
+use actix_web::test::{TestRequest, call_service, read_body};
 use bytes::{Bytes, BytesMut};
 use uuid::Uuid;
 
 #[cfg(feature = "json")]
 use serde::Serialize;
 
-#[cfg(feature = "json")]
-use serde_json;
+#[cfg(feature = "base64")]
+use base64::Engine;
+
+#[cfg(feature = "sha2")]
+use sha2::{Digest, Sha256};
+
+#[cfg(feature = "futures")]
+use futures_util::io::{AsyncRead, AsyncReadExt};
+
+#[cfg(feature = "unicode")]
+use unicode_normalization::UnicodeNormalization;
 
 /// A builder for creating `multipart/form-data` payloads for Actix-Web tests.
+#[derive(Clone)]
 pub struct TestMultipartBuilder {
     boundary: String,
     parts: Vec<Part>,
+    filename_pattern: Option<String>,
+    references: Vec<String>,
+    header_case: HeaderCase,
+    deferred: Vec<DeferredField>,
+    closing_style: ClosingStyle,
+    delimiter_dashes: usize,
+    content_derived_boundary: bool,
+    header_order: Vec<PartHeaderKind>,
+    header_spacing: HeaderSpacing,
+    leading_bom: bool,
+    interpart_crlf: bool,
+    smuggled_parts: Vec<Part>,
 }
 
 /// Represents one part of the multipart payload.
+#[derive(Clone)]
 struct Part {
     name: String,
-    content_type: String,
+    content_type: Option<String>,
     filename: Option<String>,
+    disposition_params: Vec<(String, String)>,
+    /// The name in a second, conflicting `Content-Disposition` header, if any.
+    conflicting_name: Option<String>,
+    headers: Vec<(String, String)>,
     content: Bytes,
+    param_style: ParamStyle,
+}
+
+impl Part {
+    /// A part with no extra headers or disposition parameters, escaped as usual.
+    fn new(
+        name: String,
+        content_type: Option<String>,
+        filename: Option<String>,
+        content: Bytes,
+    ) -> Self {
+        Self {
+            name,
+            content_type,
+            filename,
+            disposition_params: Vec::new(),
+            conflicting_name: None,
+            headers: Vec::new(),
+            content,
+            param_style: ParamStyle::Escaped,
+        }
+    }
+}
+
+/// How the `name` and `filename` parameters of a part's `Content-Disposition` are emitted.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ParamStyle {
+    /// Quoted, with quotes and control characters escaped, plus `filename*` for non-ASCII.
+    Escaped,
+    /// Quoted, but otherwise exactly as given.
+    Verbatim,
+    /// Wrapped in single instead of double quotes, but otherwise exactly as given.
+    SingleQuoted,
+}
+
+/// A field whose content is only computed at build time from the other parts.
+///
+/// Deferred fields are appended after all regular parts.
+#[derive(Clone)]
+enum DeferredField {
+    /// Hex-encoded SHA-256 over the contents of all other parts, including the other
+    /// deferred fields but not checksums.
+    #[cfg(feature = "sha2")]
+    Checksum(String),
+    /// Unix epoch milliseconds at build time, or the fixed value if one is given.
+    Timestamp(String, Option<u64>),
+    /// JSON list describing the name, content type, filename and length of all other parts,
+    /// including other deferred fields but not manifests.
+    #[cfg(feature = "json")]
+    Manifest(String),
+}
+
+impl DeferredField {
+    /// When the field is resolved: later stages see the resolved fields of earlier ones, so
+    /// manifests describe timestamps and checksums cover both.
+    fn stage(&self) -> u8 {
+        match *self {
+            #[cfg(feature = "sha2")]
+            DeferredField::Checksum(_) => 2,
+            DeferredField::Timestamp(..) => 0,
+            #[cfg(feature = "json")]
+            DeferredField::Manifest(_) => 1,
+        }
+    }
+
+    /// A stand-in with the right metadata for a field that is resolved in a later stage.
+    fn placeholder(&self) -> Option<Part> {
+        match *self {
+            #[cfg(feature = "sha2")]
+            DeferredField::Checksum(ref name) => Some(Part::new(
+                name.clone(),
+                Some("text/plain".to_string()),
+                None,
+                Bytes::from(vec![b'0'; 64]),
+            )),
+            _ => None,
+        }
+    }
+
+    #[cfg_attr(not(any(feature = "sha2", feature = "json")), allow(unused_variables))]
+    fn resolve(&self, parts: &[Part]) -> Part {
+        match *self {
+            #[cfg(feature = "sha2")]
+            DeferredField::Checksum(ref name) => {
+                let mut hasher = Sha256::new();
+                for part in parts {
+                    hasher.update(&part.content);
+                }
+                Part::new(
+                    name.clone(),
+                    Some("text/plain".to_string()),
+                    None,
+                    Bytes::from(format!("{:x}", hasher.finalize())),
+                )
+            }
+            DeferredField::Timestamp(ref name, fixed) => {
+                let millis = fixed.map(u128::from).unwrap_or_else(|| {
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .expect("system clock is before the Unix epoch")
+                        .as_millis()
+                });
+                Part::new(
+                    name.clone(),
+                    Some("text/plain".to_string()),
+                    None,
+                    Bytes::from(millis.to_string()),
+                )
+            }
+            #[cfg(feature = "json")]
+            DeferredField::Manifest(ref name) => {
+                let manifest: Vec<_> = parts
+                    .iter()
+                    .map(|part| {
+                        serde_json::json!({
+                            "name": part.name,
+                            "content_type": part.content_type,
+                            "filename": part.filename,
+                            "length": part.content.len(),
+                        })
+                    })
+                    .collect();
+                Part::new(
+                    name.clone(),
+                    Some("application/json".to_string()),
+                    None,
+                    Bytes::from(serde_json::to_vec(&manifest).unwrap()),
+                )
+            }
+        }
+    }
+}
+
+/// Controls how the `Content-Disposition` and `Content-Type` part header names are cased.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HeaderCase {
+    /// `Content-Disposition`, `Content-Type`.
+    #[default]
+    Canonical,
+    /// `content-disposition`, `content-type`.
+    Lowercase,
+    /// `CONTENT-DISPOSITION`, `CONTENT-TYPE`.
+    Uppercase,
+}
+
+impl HeaderCase {
+    fn apply(self, name: &str) -> String {
+        match self {
+            HeaderCase::Canonical => name.to_string(),
+            HeaderCase::Lowercase => name.to_ascii_lowercase(),
+            HeaderCase::Uppercase => name.to_ascii_uppercase(),
+        }
+    }
+}
+
+/// Controls how part header names and values are separated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HeaderSpacing {
+    /// `Content-Type: text/plain`.
+    #[default]
+    Standard,
+    /// `Content-Type:text/plain`.
+    NoSpace,
+    /// `Content-Type:   text/plain`, i.e. three spaces after the colon.
+    ExtraSpace,
+}
+
+impl HeaderSpacing {
+    fn separator(self) -> &'static str {
+        match self {
+            HeaderSpacing::Standard => ": ",
+            HeaderSpacing::NoSpace => ":",
+            HeaderSpacing::ExtraSpace => ":   ",
+        }
+    }
+}
+
+/// The kinds of headers emitted for each part, used to control their order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PartHeaderKind {
+    /// The `Content-Disposition` header.
+    ContentDisposition,
+    /// The `Content-Type` header, if the part has one.
+    ContentType,
+    /// Any additional headers attached to the part, in insertion order.
+    Custom,
+}
+
+const DEFAULT_HEADER_ORDER: [PartHeaderKind; 3] = [
+    PartHeaderKind::ContentDisposition,
+    PartHeaderKind::ContentType,
+    PartHeaderKind::Custom,
+];
+
+/// Controls how a raw field name added by `with_raw_name_field` is emitted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NameEncoding {
+    /// Emit the name exactly as given, bypassing escaping, including control characters such
+    /// as `\0`.
+    Verbatim,
+    /// Percent-encode control characters, `"` and `%` in the name, e.g. `\0` as `%00`.
+    PercentEncoded,
+}
+
+/// Controls how the closing delimiter of the body is emitted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ClosingStyle {
+    /// `--boundary--`, as required by RFC 2046.
+    #[default]
+    Correct,
+    /// `--boundary`, without the trailing dashes.
+    MissingDashes,
+    /// No closing delimiter at all.
+    Missing,
+}
+
+/// The Unicode normalization form applied by `with_filename_normalized`.
+#[cfg(feature = "unicode")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NormalizationForm {
+    /// Canonical composition, e.g. `é` as a single code point.
+    Nfc,
+    /// Canonical decomposition, e.g. `é` as `e` followed by a combining acute accent.
+    Nfd,
+}
+
+/// What a correct parser should extract from one part of a built payload.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpectedPart {
+    pub name: String,
+    /// `None` when the part has no (or an empty) `Content-Type` header.
+    pub content_type: Option<String>,
+    pub filename: Option<String>,
+    pub content: Bytes,
 }
 
 impl TestMultipartBuilder {
@@ -118,7 +401,154 @@ impl TestMultipartBuilder {
         Self {
             boundary: Uuid::new_v4().to_string(),
             parts: Vec::new(),
+            filename_pattern: None,
+            references: Vec::new(),
+            header_case: HeaderCase::default(),
+            deferred: Vec::new(),
+            closing_style: ClosingStyle::default(),
+            delimiter_dashes: 2,
+            content_derived_boundary: false,
+            header_order: DEFAULT_HEADER_ORDER.to_vec(),
+            header_spacing: HeaderSpacing::default(),
+            leading_bom: false,
+            interpart_crlf: true,
+            smuggled_parts: Vec::new(),
+        }
+    }
+
+    /// Use a boundary made of `digits` random decimal digits.
+    ///
+    /// Panics if `digits` is outside the 1 to 70 characters allowed by RFC 2046.
+    pub fn with_numeric_boundary(mut self, digits: usize) -> Self {
+        assert!(
+            (1..=70).contains(&digits),
+            "boundary must be between 1 and 70 characters, got {digits}"
+        );
+        let mut boundary = String::with_capacity(digits);
+        while boundary.len() < digits {
+            for byte in Uuid::new_v4().into_bytes() {
+                if boundary.len() == digits {
+                    break;
+                }
+                boundary.push(char::from(b'0' + byte % 10));
+            }
+        }
+        self.boundary = boundary;
+        self
+    }
+
+    /// Use a random boundary of the maximum 70 characters allowed by RFC 2046, drawn from
+    /// its full `bcharsnospace` set.
+    pub fn with_max_length_boundary(mut self) -> Self {
+        let alphabet: Vec<u8> = (b'0'..=b'9')
+            .chain(b'A'..=b'Z')
+            .chain(b'a'..=b'z')
+            .chain(BCHARS_NOSPACE.bytes())
+            .collect();
+        let mut boundary = String::with_capacity(70);
+        while boundary.len() < 70 {
+            for byte in Uuid::new_v4().into_bytes() {
+                if boundary.len() == 70 {
+                    break;
+                }
+                boundary.push(char::from(alphabet[usize::from(byte) % alphabet.len()]));
+            }
         }
+        self.boundary = boundary;
+        self
+    }
+
+    /// Generate filenames for file parts that were added with an empty filename.
+    ///
+    /// The pattern is applied at build time. `{index}` is replaced with the position of the
+    /// part among those unnamed file parts (starting at 0) and `{name}` with the field name,
+    /// e.g. `upload_{index}.bin` yields `upload_0.bin`, `upload_1.bin`, ...
+    pub fn with_filename_pattern(mut self, pattern: &str) -> Self {
+        self.filename_pattern = Some(pattern.to_string());
+        self
+    }
+
+    /// Set the casing of the `Content-Disposition` and `Content-Type` part header names.
+    pub fn with_header_case(mut self, case: HeaderCase) -> Self {
+        self.header_case = case;
+        self
+    }
+
+    /// Set how the closing delimiter is emitted, e.g. to reproduce a client that forgot it.
+    pub fn with_malformed_closing(mut self, style: ClosingStyle) -> Self {
+        self.closing_style = style;
+        self
+    }
+
+    /// Set how many dashes prefix each boundary delimiter in the body (2 by default).
+    ///
+    /// The boundary in the `Content-Type` header is unaffected.
+    pub fn with_delimiter_dash_count(mut self, count: usize) -> Self {
+        self.delimiter_dashes = count;
+        self
+    }
+
+    /// Use a boundary full of regex metacharacters (`.`, `+`, `?`, `(`, `)`), to catch handlers
+    /// that treat the boundary as a pattern instead of a literal.
+    ///
+    /// Only characters from RFC 2046 `bcharsnospace` are used, so `*` is never included.
+    /// The boundary is quoted in the `Content-Type` header since some of them are `tspecials`.
+    pub fn with_regex_unsafe_boundary(mut self) -> Self {
+        self.boundary = format!("(.+)?{}.+?(x)", Uuid::new_v4().simple());
+        self
+    }
+
+    /// Emit a UTF-8 byte order mark (`EF BB BF`) before the first delimiter line, as sent by
+    /// some buggy clients.
+    pub fn with_leading_bom(mut self) -> Self {
+        self.leading_bom = true;
+        self
+    }
+
+    /// Omit the CRLF that must precede every delimiter after a part's content, as buggy
+    /// clients do, producing run-on parts. Parts whose content already ends in CRLF still get
+    /// the delimiter CRLF, so they arrive intact.
+    pub fn without_interpart_crlf(mut self) -> Self {
+        self.interpart_crlf = false;
+        self
+    }
+
+    /// Append a complete text part after the closing delimiter, followed by another closing
+    /// delimiter. Compliant parsers treat it as epilogue and must ignore it.
+    pub fn with_smuggled_trailing_part(mut self, name: &str, content: impl Into<Bytes>) -> Self {
+        self.smuggled_parts.push(Part::new(
+            name.to_string(),
+            Some("text/plain".to_string()),
+            None,
+            content.into(),
+        ));
+        self
+    }
+
+    /// Derive the boundary at build time from a hash of all regular part contents.
+    ///
+    /// Builders with identical part contents produce identical boundaries, and thus
+    /// identical bodies. Build-time fields such as timestamps are left out of the hash, so
+    /// the boundary stays the same across builds. The boundary is guaranteed not to occur in
+    /// any part's content.
+    pub fn with_content_derived_boundary(mut self) -> Self {
+        self.content_derived_boundary = true;
+        self
+    }
+
+    /// Set how the name and value of each part header are separated.
+    pub fn with_header_spacing(mut self, style: HeaderSpacing) -> Self {
+        self.header_spacing = style;
+        self
+    }
+
+    /// Set the order in which each part's headers are emitted.
+    ///
+    /// Defaults to disposition, then content type, then custom headers. Kinds missing from
+    /// `order` are emitted afterwards in their default order.
+    pub fn with_header_order(mut self, order: &[PartHeaderKind]) -> Self {
+        self.header_order = order.to_vec();
+        self
     }
 
     /// Add a simple text part (e.g., "text/plain").
@@ -131,6 +561,134 @@ impl TestMultipartBuilder {
         )
     }
 
+    /// Add a text field without a `Content-Type` header, as browsers send plain inputs.
+    pub fn with_field(mut self, name: &str, value: &str) -> Self {
+        self.parts.push(Part::new(
+            name.to_string(),
+            None,
+            None,
+            Bytes::from(value.to_string()),
+        ));
+        self
+    }
+
+    /// Add a text field whose value is the name of another part.
+    ///
+    /// Building panics if no part named `target_name` exists by then. Build-time fields such
+    /// as timestamps count as parts.
+    pub fn with_reference_field(mut self, name: &str, target_name: &str) -> Self {
+        self.references.push(target_name.to_string());
+        self.with_text(name, target_name)
+    }
+
+    /// Add the fields a browser submits on its own for a typical form.
+    ///
+    /// Currently this is a single `_charset_` field with the value `UTF-8` and, as browsers
+    /// send it, no `Content-Type`, added at the current position like any other part.
+    pub fn with_browser_defaults(self) -> Self {
+        self.with_field("_charset_", "UTF-8")
+    }
+
+    /// Add a text field whose name may contain control characters such as `\0`.
+    ///
+    /// This is a security-testing tool: some parsers truncate names at null bytes, so a
+    /// handler should reject or safely handle such names.
+    pub fn with_raw_name_field(self, name: &str, value: &str, encoding: NameEncoding) -> Self {
+        let name = match encoding {
+            NameEncoding::Verbatim => name.to_string(),
+            NameEncoding::PercentEncoded => {
+                let mut encoded = String::with_capacity(name.len());
+                for c in name.chars() {
+                    if c.is_ascii_control() || c == '"' || c == '%' {
+                        encoded.push_str(&format!("%{:02X}", c as u32));
+                    } else {
+                        encoded.push(c);
+                    }
+                }
+                encoded
+            }
+        };
+        let mut builder = self.with_part(
+            name,
+            "text/plain".to_string(),
+            None,
+            Bytes::from(value.to_string()),
+        );
+        builder.last_part_mut().param_style = ParamStyle::Verbatim;
+        builder
+    }
+
+    /// Add a text part whose content contains `--` followed by all but the last character
+    /// of the boundary, to check that parsers don't stop early on near matches.
+    ///
+    /// The content is derived from the boundary configured at the time of the call.
+    pub fn with_near_boundary_content(self, name: &str) -> Self {
+        let prefix = &self.boundary[..self.boundary.len().saturating_sub(1)];
+        let content = format!("start\r\n--{prefix}\r\nmiddle --{prefix}-- end");
+        self.with_text(name, &content)
+    }
+
+    /// Add a binary part whose content is full of `\r\n--` sequences, none of which is
+    /// followed by the boundary, to stress a parser's boundary matching.
+    pub fn with_crlf_heavy_content(self, name: &str) -> Self {
+        let mut content = BytesMut::new();
+        for suffix in ["", "-", "--", "\r\n", "x", "\0"] {
+            content.extend_from_slice(b"\r\n--");
+            content.extend_from_slice(suffix.as_bytes());
+        }
+        content.extend_from_slice(b"\r\n\r\n--\r\n");
+        self.with_bytes(
+            name,
+            "crlf.bin",
+            "application/octet-stream",
+            content.freeze(),
+        )
+    }
+
+    /// Add text parts in exactly the given `(name, value)` order, e.g. to interleave
+    /// same-named fields as `a, b, a, b`.
+    pub fn with_interleaved(self, pairs: &[(&str, &str)]) -> Self {
+        pairs.iter().fold(self, |builder, (name, value)| {
+            builder.with_text(name, value)
+        })
+    }
+
+    /// Add `count` tiny text parts named `<name_prefix>_0` to `<name_prefix>_<count - 1>`, to
+    /// probe part-count limits.
+    pub fn with_max_parts(self, name_prefix: &str, count: usize) -> Self {
+        (0..count).fold(self, |builder, index| {
+            builder.with_text(&format!("{}_{}", name_prefix, index), &index.to_string())
+        })
+    }
+
+    /// Add a text field using the PHP/Rails array naming convention: `base_name[]` when
+    /// `index` is `None`, or `base_name[<index>]` otherwise.
+    pub fn with_indexed_field(self, base_name: &str, index: Option<usize>, value: &str) -> Self {
+        let name = match index {
+            Some(index) => format!("{}[{}]", base_name, index),
+            None => format!("{}[]", base_name),
+        };
+        self.with_text(&name, value)
+    }
+
+    /// Add a text field holding the current Unix epoch milliseconds, taken at build time.
+    ///
+    /// Like other build-time fields, it is appended after all regular parts.
+    pub fn with_timestamp_field(mut self, name: &str) -> Self {
+        self.deferred
+            .push(DeferredField::Timestamp(name.to_string(), None));
+        self
+    }
+
+    /// Add a text field holding the given Unix epoch milliseconds, for deterministic tests.
+    ///
+    /// It is placed exactly like `with_timestamp_field`, after all regular parts.
+    pub fn with_timestamp_field_at(mut self, name: &str, millis: u64) -> Self {
+        self.deferred
+            .push(DeferredField::Timestamp(name.to_string(), Some(millis)));
+        self
+    }
+
     /// Add a part from in-memory bytes (e.g., a file).
     pub fn with_bytes(
         self,
@@ -146,157 +704,3413 @@ impl TestMultipartBuilder {
             content.into(),
         )
     }
-    
-    /// Add a serializable JSON part with "application/json".
+
+    /// Add a zero-length file part that still declares `filename` and `content_type`.
+    pub fn with_empty_file(self, name: &str, filename: &str, content_type: &str) -> Self {
+        self.with_bytes(name, filename, content_type, Bytes::new())
+    }
+
+    /// Add a file part whose filename is normalized to `form` before being emitted.
     ///
-    /// This method is only available when the `json` feature is enabled.
-    #[cfg(feature = "json")]
-    pub fn with_json<T: Serialize>(self, name: &str, json_data: &T) -> Self {
-        let content = serde_json::to_vec(json_data).unwrap();
+    /// This method is only available when the `unicode` feature is enabled.
+    #[cfg(feature = "unicode")]
+    pub fn with_filename_normalized(
+        self,
+        name: &str,
+        filename: &str,
+        form: NormalizationForm,
+        content_type: &str,
+        content: impl Into<Bytes>,
+    ) -> Self {
+        let filename: String = match form {
+            NormalizationForm::Nfc => filename.nfc().collect(),
+            NormalizationForm::Nfd => filename.nfd().collect(),
+        };
+        self.with_bytes(name, &filename, content_type, content)
+    }
+
+    /// Add a text part declared as `text/plain; charset=<declared_charset>` whose content is
+    /// sent as-is, whether or not it is valid in that charset.
+    pub fn with_charset_mismatch(
+        self,
+        name: &str,
+        declared_charset: &str,
+        actual_bytes: impl Into<Bytes>,
+    ) -> Self {
         self.with_part(
             name.to_string(),
-            "application/json".to_string(),
-            None, // JSON parts typically don't have a filename
-            Bytes::from(content),
+            format!("text/plain; charset={}", declared_charset),
+            None,
+            actual_bytes.into(),
         )
     }
 
-    /// The generic "add part" method.
-    pub fn with_part(
+    /// Add a text part whose disposition wraps the name in single quotes, as in
+    /// `form-data; name='<name>'`, as sent by some noncompliant clients.
+    pub fn with_single_quoted_disposition(self, name: &str, content: impl Into<Bytes>) -> Self {
+        let mut builder = self.with_part(
+            name.to_string(),
+            "text/plain".to_string(),
+            None,
+            content.into(),
+        );
+        builder.last_part_mut().param_style = ParamStyle::SingleQuoted;
+        builder
+    }
+
+    /// Add a part whose `Content-Type` header is present but has an empty value.
+    ///
+    /// Unlike `with_file_no_content_type`, the header line itself is still emitted.
+    pub fn with_empty_content_type_part(self, name: &str, content: impl Into<Bytes>) -> Self {
+        self.with_part(name.to_string(), String::new(), None, content.into())
+    }
+
+    /// Add a file part that has a filename but no `Content-Type` header at all.
+    pub fn with_file_no_content_type(
         mut self,
-        name: String,
-        content_type: String,
-        filename: Option<String>,
-        content: Bytes,
+        name: &str,
+        filename: &str,
+        content: impl Into<Bytes>,
     ) -> Self {
-        self.parts.push(Part {
-            name,
-            content_type,
-            filename,
-            content,
-        });
+        self.parts.push(Part::new(
+            name.to_string(),
+            None,
+            Some(filename.to_string()),
+            content.into(),
+        ));
         self
     }
 
-    /// Build the final (HeaderValue, Bytes) tuple for the test request.
-    pub fn build(self) -> ((HeaderName, HeaderValue), Bytes) {
-        let mut body = BytesMut::new();
-
-        for part in self.parts {
-            body.extend_from_slice(format!("--{}\r\n", self.boundary).as_bytes());
-            
-            let disposition = if let Some(filename) = part.filename {
-                format!(
-                    "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n",
-                    part.name, filename
-                )
-            } else {
-                format!("Content-Disposition: form-data; name=\"{}\"\r\n", part.name)
-            };
-            body.extend_from_slice(disposition.as_bytes());
-
-            body.extend_from_slice(
-                format!("Content-Type: {}\r\n\r\n", part.content_type).as_bytes(),
-            );
-            body.extend_from_slice(&part.content);
-            body.extend_from_slice("\r\n".as_bytes());
-        }
+    /// Add a part whose content type is emitted verbatim, including any surrounding
+    /// whitespace, e.g. `"  application/json "`.
+    pub fn with_untrimmed_content_type(
+        self,
+        name: &str,
+        content_type_with_spaces: &str,
+        content: impl Into<Bytes>,
+    ) -> Self {
+        self.with_part(
+            name.to_string(),
+            content_type_with_spaces.to_string(),
+            None,
+            content.into(),
+        )
+    }
 
-        body.extend_from_slice(format!("--{}--\r\n", self.boundary).as_bytes());
+    /// Add a tiny `video/mp4` file consisting of a valid `ftyp` box, enough to pass
+    /// content sniffing without shipping a real video fixture.
+    pub fn with_test_mp4(self, name: &str, filename: &str) -> Self {
+        const MP4: &[u8] = &[
+            0x00, 0x00, 0x00, 0x18, b'f', b't', b'y', b'p', // box size (24) and type
+            b'i', b's', b'o', b'm', 0x00, 0x00, 0x02, 0x00, // major brand and minor version
+            b'i', b's', b'o', b'm', b'm', b'p', b'4', b'1', // compatible brands
+        ];
+        self.with_bytes(name, filename, "video/mp4", Bytes::from_static(MP4))
+    }
 
-        let content_type_value =
-            HeaderValue::from_str(&format!("multipart/form-data; boundary={}", self.boundary))
-                .unwrap();
+    /// Add a text part carrying two `Content-Disposition` headers: the regular one naming
+    /// `real_name`, followed by a second one naming `fake_name`.
+    pub fn with_conflicting_disposition(
+        self,
+        real_name: &str,
+        fake_name: &str,
+        content: impl Into<Bytes>,
+    ) -> Self {
+        let mut builder = self.with_part(
+            real_name.to_string(),
+            "text/plain".to_string(),
+            None,
+            content.into(),
+        );
+        builder.last_part_mut().conflicting_name = Some(fake_name.to_string());
+        builder
+    }
 
-        ((CONTENT_TYPE, content_type_value), body.freeze())
+    /// Add a file part whose filename is emitted verbatim, including any `../` or `..\`
+    /// sequences.
+    ///
+    /// The builder does not sanitize the filename, which makes this suitable for testing
+    /// that a handler protects against path traversal.
+    pub fn with_traversal_filename(
+        self,
+        name: &str,
+        filename: &str,
+        content_type: &str,
+        content: impl Into<Bytes>,
+    ) -> Self {
+        let mut builder = self.with_bytes(name, filename, content_type, content);
+        builder.last_part_mut().param_style = ParamStyle::Verbatim;
+        builder
     }
-}
 
-impl Default for TestMultipartBuilder {
-    
-    #[inline(always)]
-    fn default() -> Self {
-        Self::new()
+    /// Add a file part with the wildcard content type `*/*`, as sent by some mobile clients
+    /// for unknown files.
+    pub fn with_wildcard_content_type_part(
+        self,
+        name: &str,
+        filename: &str,
+        content: impl Into<Bytes>,
+    ) -> Self {
+        self.with_bytes(name, filename, "*/*", content)
     }
-}
 
-#[cfg(test)]
-mod test {
-    use actix_multipart::form::{
+    /// Add a file part whose filename extension, declared content type and actual content
+    /// deliberately disagree, e.g. `photo.png` declared as `text/plain` containing JPEG bytes.
+    ///
+    /// This behaves like `with_bytes` and exists to document the intent of sniffing tests.
+    pub fn with_mismatched_file(
+        self,
+        name: &str,
+        filename: &str,
+        declared_content_type: &str,
+        content: impl Into<Bytes>,
+    ) -> Self {
+        self.with_bytes(name, filename, declared_content_type, content)
+    }
+
+    /// Add a serializable JSON part with "application/json".
+    ///
+    /// This method is only available when the `json` feature is enabled.
+    #[cfg(feature = "json")]
+    pub fn with_json<T: Serialize>(self, name: &str, json_data: &T) -> Self {
+        let content = serde_json::to_vec(json_data).unwrap();
+        self.with_part(
+            name.to_string(),
+            "application/json".to_string(),
+            None, // JSON parts typically don't have a filename
+            Bytes::from(content),
+        )
+    }
+
+    /// Declare the content length of the named part via an `X-Upload-Length` part header.
+    ///
+    /// The declared value is the part's length at the time of the call. Panics if no part
+    /// with that name has been added yet.
+    pub fn with_declared_upload_length(self, name: &str) -> Self {
+        let length = self
+            .parts
+            .iter()
+            .find(|part| part.name == name)
+            .map(|part| part.content.len() as u64)
+            .unwrap_or_else(|| panic!("no part named \"{name}\" to declare a length for"));
+        self.with_declared_upload_length_as(name, length)
+    }
+
+    /// Declare an arbitrary (possibly wrong) content length for the named part.
+    ///
+    /// Useful for negative tests of handlers that compare the declared length with the
+    /// bytes actually received. Panics if no part with that name has been added yet.
+    pub fn with_declared_upload_length_as(mut self, name: &str, length: u64) -> Self {
+        let part = self
+            .parts
+            .iter_mut()
+            .find(|part| part.name == name)
+            .unwrap_or_else(|| panic!("no part named \"{name}\" to declare a length for"));
+        part.headers
+            .push(("X-Upload-Length".to_string(), length.to_string()));
+        self
+    }
+
+    /// Add a binary part from base64-encoded content.
+    ///
+    /// The content is decoded before being added, so the part carries the raw bytes rather
+    /// than a base64 transfer encoding.
+    ///
+    /// This method is only available when the `base64` feature is enabled.
+    #[cfg(feature = "base64")]
+    pub fn with_base64_content(
+        self,
+        name: &str,
+        filename: &str,
+        content_type: &str,
+        base64_str: &str,
+    ) -> Result<Self, base64::DecodeError> {
+        let content = base64::engine::general_purpose::STANDARD.decode(base64_str)?;
+        Ok(self.with_bytes(name, filename, content_type, content))
+    }
+
+    /// Add a text field holding the hex-encoded SHA-256 of all other parts' contents.
+    ///
+    /// The digest is computed at build time over the contents of all other parts in body
+    /// order, including other build-time fields such as manifests, but not checksums. The
+    /// field is appended after the regular parts.
+    ///
+    /// This method is only available when the `sha2` feature is enabled.
+    #[cfg(feature = "sha2")]
+    pub fn with_aggregate_checksum_field(mut self, name: &str) -> Self {
+        self.deferred
+            .push(DeferredField::Checksum(name.to_string()));
+        self
+    }
+
+    /// Add a JSON field listing the name, content type, filename and byte length of every
+    /// other part.
+    ///
+    /// The manifest is computed at build time and appended after the regular parts. It also
+    /// describes other build-time fields such as timestamps and checksums, but not itself.
+    ///
+    /// This method is only available when the `json` feature is enabled.
+    #[cfg(feature = "json")]
+    pub fn with_manifest_field(mut self, name: &str) -> Self {
+        self.deferred
+            .push(DeferredField::Manifest(name.to_string()));
+        self
+    }
+
+    /// Append a custom `; key="value"` parameter to the most recently added part's
+    /// `Content-Disposition`, e.g. `category="avatar"`. The value is quoted and escaped.
+    ///
+    /// Panics if no part has been added yet or if `key` is not an RFC 7230 token.
+    pub fn with_disposition_param(mut self, key: &str, value: &str) -> Self {
+        assert!(is_token(key), "invalid disposition parameter name {key:?}");
+        self.last_part_mut()
+            .disposition_params
+            .push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Attach an arbitrary header to the most recently added part, e.g.
+    /// `Content-Transfer-Encoding: binary`. Headers are emitted in insertion order, with the
+    /// name cased exactly as given regardless of `with_header_case`.
+    ///
+    /// Panics if no part has been added yet.
+    pub fn with_part_header(mut self, header_name: &str, value: &str) -> Self {
+        self.last_part_mut()
+            .headers
+            .push((header_name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Attach a header whose value is `value_len` repeated `x` characters to the most recently
+    /// added part, to probe a parser's header length limit.
+    ///
+    /// Panics if no part has been added yet.
+    pub fn with_long_header(mut self, header_name: &str, value_len: usize) -> Self {
+        self.last_part_mut()
+            .headers
+            .push((header_name.to_string(), "x".repeat(value_len)));
+        self
+    }
+
+    /// Attach a header folded across two lines (`line1\r\n\t line2`) to the most recently
+    /// added part, as emitted by legacy MIME clients. Modern HTTP forbids folding.
+    ///
+    /// Panics if no part has been added yet.
+    pub fn with_folded_header(mut self, header_name: &str, line1: &str, line2: &str) -> Self {
+        self.last_part_mut()
+            .headers
+            .push((header_name.to_string(), format!("{line1}\r\n\t {line2}")));
+        self
+    }
+
+    /// Attach `X-Chunk-Index` and `X-Total-Chunks` headers to the most recently added part,
+    /// as used by resumable upload protocols.
+    ///
+    /// Panics if no part has been added yet or if `index` is not below `total`.
+    pub fn with_chunk_metadata(mut self, index: usize, total: usize) -> Self {
+        assert!(
+            index < total,
+            "chunk index {index} is out of range for {total} chunks"
+        );
+        let part = self.last_part_mut();
+        part.headers
+            .push(("X-Chunk-Index".to_string(), index.to_string()));
+        part.headers
+            .push(("X-Total-Chunks".to_string(), total.to_string()));
+        self
+    }
+
+    /// Read a file from disk and add it as a file part.
+    ///
+    /// The filename is taken from `path` and the content type is inferred from its extension,
+    /// falling back to `application/octet-stream`. Errors mention the path that failed.
+    pub fn with_file(self, name: &str, path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read(path)
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))?;
+        let filename = path
+            .file_name()
+            .map(|filename| filename.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        Ok(self.with_bytes(name, &filename, content_type_for_path(path), content))
+    }
+
+    /// Like `with_file`, but panics if the file can't be read.
+    pub fn with_file_unwrap(self, name: &str, path: impl AsRef<Path>) -> Self {
+        self.with_file(name, path)
+            .unwrap_or_else(|err| panic!("failed to read fixture {err}"))
+    }
+
+    /// Add a file part backed by a memory-mapped file, for large fixtures that should not be
+    /// read into memory.
+    ///
+    /// The filename is taken from `path` and the content type is inferred from its extension,
+    /// falling back to `application/octet-stream`. The file must not be modified while the
+    /// builder or its output is alive.
+    ///
+    /// This method is only available when the `mmap` feature is enabled.
+    #[cfg(feature = "mmap")]
+    pub fn with_mmap_file(self, name: &str, path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        // SAFETY: the caller is responsible for not modifying the file while it is mapped
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        let filename = path
+            .file_name()
+            .map(|filename| filename.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        Ok(self.with_bytes(
+            name,
+            &filename,
+            content_type_for_path(path),
+            Bytes::from_owner(mmap),
+        ))
+    }
+
+    /// Add a file part whose content is read to completion from an async reader.
+    ///
+    /// This method is only available when the `futures` feature is enabled.
+    #[cfg(feature = "futures")]
+    pub async fn with_async_read(
+        self,
+        name: &str,
+        filename: &str,
+        content_type: &str,
+        mut reader: impl AsyncRead + Unpin,
+    ) -> io::Result<Self> {
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content).await?;
+        Ok(self.with_bytes(name, filename, content_type, content))
+    }
+
+    /// Attach a `Content-Range: bytes <start>-<end>/<total>` header to the most recently
+    /// added part.
+    ///
+    /// Panics if no part has been added yet or unless `start <= end < total`.
+    pub fn with_content_range(mut self, start: u64, end: u64, total: u64) -> Self {
+        assert!(
+            start <= end && end < total,
+            "invalid content range {start}-{end}/{total}"
+        );
+        self.last_part_mut().headers.push((
+            "Content-Range".to_string(),
+            format!("bytes {}-{}/{}", start, end, total),
+        ));
+        self
+    }
+
+    /// Add a part that is itself a multipart body of the given subtype (e.g. `mixed`,
+    /// `related` or `alternative`), built from `inner`.
+    ///
+    /// The inner boundary is wired into the part's `multipart/<subtype>` content type. Note
+    /// that `actix_multipart::Multipart` rejects such parts with `MultipartError::Nested`.
+    pub fn with_nested_typed(self, name: &str, subtype: &str, inner: TestMultipartBuilder) -> Self {
+        let (boundary, body) = inner.serialize();
+        self.with_part(
+            name.to_string(),
+            format!(
+                "multipart/{}; boundary={}",
+                subtype,
+                boundary_param(&boundary)
+            ),
+            None,
+            body,
+        )
+    }
+
+    /// The generic "add part" method.
+    pub fn with_part(
+        mut self,
+        name: String,
+        content_type: String,
+        filename: Option<String>,
+        content: Bytes,
+    ) -> Self {
+        self.parts
+            .push(Part::new(name, Some(content_type), filename, content));
+        self
+    }
+
+    /// Check that every part's content type parses as a valid MIME type.
+    ///
+    /// On failure, returns the `(field name, content type)` pairs that did not parse. Parts
+    /// without a content type are skipped.
+    ///
+    /// This method is only available when the `mime` feature is enabled.
+    #[cfg(feature = "mime")]
+    pub fn validate_content_types(&self) -> Result<(), Vec<(String, String)>> {
+        let invalid: Vec<_> = self
+            .parts
+            .iter()
+            .filter_map(|part| {
+                let content_type = part.content_type.as_ref()?;
+                match content_type.parse::<mime::Mime>() {
+                    Ok(mime) if !mime.subtype().as_str().is_empty() => None,
+                    _ => Some((part.name.clone(), content_type.clone())),
+                }
+            })
+            .collect();
+
+        if invalid.is_empty() {
+            Ok(())
+        } else {
+            Err(invalid)
+        }
+    }
+
+    /// Map each field name to the content types of its parts, in order.
+    ///
+    /// Parts without a content type are left out.
+    pub fn content_type_map(&self) -> HashMap<String, Vec<String>> {
+        let mut map: HashMap<String, Vec<String>> = HashMap::new();
+        for part in self.resolved_parts() {
+            if let Some(content_type) = part.content_type {
+                map.entry(part.name).or_default().push(content_type);
+            }
+        }
+        map
+    }
+
+    /// The size of the serialized body in bytes.
+    pub fn byte_len(&self) -> usize {
+        self.serialize().1.len()
+    }
+
+    /// The `Content-Type` header for the payload, without consuming the builder.
+    pub fn content_type_header(&self) -> (HeaderName, HeaderValue) {
+        let boundary = self.boundary_for(&self.resolved_parts());
+        (CONTENT_TYPE, content_type_value(&boundary))
+    }
+
+    /// The `Content-Length` header for the payload, without consuming the builder.
+    pub fn content_length_header(&self) -> (HeaderName, HeaderValue) {
+        (CONTENT_LENGTH, HeaderValue::from(self.byte_len()))
+    }
+
+    /// The combined size of all part contents, excluding any multipart framing.
+    pub fn total_content_size(&self) -> usize {
+        self.resolved_parts()
+            .iter()
+            .map(|part| part.content.len())
+            .sum()
+    }
+
+    /// The number of bytes the multipart framing adds on top of the part contents
+    /// (boundaries, part headers, separators and the closing delimiter).
+    pub fn framing_overhead(&self) -> usize {
+        self.byte_len() - self.total_content_size()
+    }
+
+    /// Render the full request as raw HTTP/1.1 bytes, e.g. for socket-level tests.
+    ///
+    /// The output contains the request line, `Host`, `Content-Type` and `Content-Length`
+    /// headers, followed by `extra_headers`, a blank line and the multipart body.
+    pub fn to_raw_http_request(
+        &self,
+        method: &str,
+        path: &str,
+        extra_headers: &[(&str, &str)],
+    ) -> Bytes {
+        let (boundary, body) = self.serialize();
+        let mut request = BytesMut::new();
+
+        request.extend_from_slice(format!("{} {} HTTP/1.1\r\n", method, path).as_bytes());
+        request.extend_from_slice("Host: localhost\r\n".as_bytes());
+        request.extend_from_slice(
+            format!(
+                "Content-Type: {}\r\n",
+                content_type_value(&boundary).to_str().unwrap()
+            )
+            .as_bytes(),
+        );
+        request.extend_from_slice(format!("Content-Length: {}\r\n", body.len()).as_bytes());
+        for (name, value) in extra_headers {
+            request.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+        }
+        request.extend_from_slice("\r\n".as_bytes());
+        request.extend_from_slice(&body);
+
+        request.freeze()
+    }
+
+    /// Reorder the parts so that all non-file parts (those without a filename) come first,
+    /// followed by the file parts. The relative order within each group is preserved.
+    pub fn group_by_kind(mut self) -> Self {
+        self.parts.sort_by_key(|part| part.filename.is_some());
+        self
+    }
+
+    /// Move the first part named `name` to the front, leaving the others in place.
+    ///
+    /// Does nothing if no such part exists.
+    pub fn pin_first(mut self, name: &str) -> Self {
+        if let Some(index) = self.parts.iter().position(|part| part.name == name) {
+            let part = self.parts.remove(index);
+            self.parts.insert(0, part);
+        }
+        self
+    }
+
+    /// Keep only the first `n` parts, e.g. to bisect a failing fixture by part count.
+    ///
+    /// Deferred fields such as checksums are still appended and computed over the kept parts.
+    pub fn take_parts(mut self, n: usize) -> Self {
+        self.parts.truncate(n);
+        self
+    }
+
+    /// Build the final (HeaderValue, Bytes) tuple for the test request.
+    pub fn build(self) -> ((HeaderName, HeaderValue), Bytes) {
+        let (boundary, body) = self.serialize();
+        ((CONTENT_TYPE, content_type_value(&boundary)), body)
+    }
+
+    /// Build the parts as an `application/x-www-form-urlencoded` body instead, as
+    /// `name=content` pairs joined by `&`. Filenames and part headers are dropped.
+    ///
+    /// Names and contents are escaped, so `=`, `&`, `+` and `%` round-trip through `web::Form`.
+    pub fn build_urlencoded(self) -> ((HeaderName, HeaderValue), Bytes) {
+        let parts = self.resolved_parts();
+        self.check_references(&parts);
+        let body = parts
+            .iter()
+            .map(|part| {
+                format!(
+                    "{}={}",
+                    form_urlencode(part.name.as_bytes()),
+                    form_urlencode(&part.content)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+        (
+            (
+                CONTENT_TYPE,
+                HeaderValue::from_static("application/x-www-form-urlencoded"),
+            ),
+            Bytes::from(body),
+        )
+    }
+
+    /// Build the payload together with the SHA-256 digest of the body bytes.
+    ///
+    /// This method is only available when the `sha2` feature is enabled.
+    #[cfg(feature = "sha2")]
+    pub fn build_with_digest(self) -> (((HeaderName, HeaderValue), Bytes), [u8; 32]) {
+        let (content_type, body) = self.build();
+        let digest = Sha256::digest(&body).into();
+        ((content_type, body), digest)
+    }
+
+    /// Build just the `Content-Type` value and the raw body, for handlers that take the whole
+    /// body through a `web::Bytes` extractor instead of parsing it with `Multipart`.
+    ///
+    /// The body is identical to `build()`'s; only the header name is left out.
+    pub fn into_raw_payload(self) -> (HeaderValue, Bytes) {
+        let ((_, content_type), body) = self.build();
+        (content_type, body)
+    }
+
+    /// Build the payload as a plain content type string and body bytes, for use with
+    /// `async-std`/`tide` or any other runtime that doesn't speak actix types.
+    #[cfg(feature = "async-std")]
+    pub fn into_async_std_body(self) -> (String, Vec<u8>) {
+        let ((_, content_type), body) = self.build();
+        (content_type.to_str().unwrap().to_string(), body.to_vec())
+    }
+
+    /// Build the payload together with the parts a correct parser should extract from it, in order.
+    pub fn build_with_expectation(self) -> (((HeaderName, HeaderValue), Bytes), Vec<ExpectedPart>) {
+        let parts = self.resolved_parts();
+        let (boundary, body) = self.serialize_parts(&parts);
+        let expected = parts
+            .into_iter()
+            .map(|part| ExpectedPart {
+                name: part.name,
+                content_type: part
+                    .content_type
+                    .filter(|content_type| !content_type.is_empty()),
+                filename: part.filename,
+                content: part.content,
+            })
+            .collect();
+        (
+            ((CONTENT_TYPE, content_type_value(&boundary)), body),
+            expected,
+        )
+    }
+
+    /// Compare the serialized body byte-for-byte against the snapshot file at `path`, writing
+    /// the file instead if it doesn't exist yet.
+    ///
+    /// The body is built with a content-derived boundary, so snapshots are only stable for
+    /// builders whose content is deterministic (no timestamp fields, for example). Panics with
+    /// a line diff on mismatch.
+    pub fn assert_matches_snapshot(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        let (_, actual) = self.clone().with_content_derived_boundary().serialize();
+
+        let expected = match fs::read(path) {
+            Ok(expected) => expected,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                fs::write(path, &actual).unwrap_or_else(|err| {
+                    panic!("failed to write snapshot {}: {err}", path.display())
+                });
+                return;
+            }
+            Err(err) => panic!("failed to read snapshot {}: {err}", path.display()),
+        };
+        if expected == actual {
+            return;
+        }
+
+        let expected = String::from_utf8_lossy(&expected);
+        let actual = String::from_utf8_lossy(&actual);
+        let (expected_lines, actual_lines): (Vec<_>, Vec<_>) = (
+            expected.split("\r\n").collect(),
+            actual.split("\r\n").collect(),
+        );
+        let mut diff = String::new();
+        for index in 0..expected_lines.len().max(actual_lines.len()) {
+            match (expected_lines.get(index), actual_lines.get(index)) {
+                (Some(expected), Some(actual)) if expected == actual => {
+                    diff.push_str(&format!("  {expected}\n"))
+                }
+                (expected, actual) => {
+                    if let Some(expected) = expected {
+                        diff.push_str(&format!("- {expected}\n"));
+                    }
+                    if let Some(actual) = actual {
+                        diff.push_str(&format!("+ {actual}\n"));
+                    }
+                }
+            }
+        }
+        panic!("body does not match snapshot {}:\n{diff}", path.display());
+    }
+
+    /// Build a POST `TestRequest` carrying the payload and an `Authorization: Bearer` header.
+    pub fn into_authed_request(self, bearer_token: &str) -> TestRequest {
+        let (content_type, body) = self.build();
+        TestRequest::post()
+            .insert_header(content_type)
+            .insert_header((AUTHORIZATION, format!("Bearer {}", bearer_token)))
+            .set_payload(body)
+    }
+
+    /// POST the payload to `uri` on `app` and assert that the response is 2xx.
+    ///
+    /// Panics with the status and response body otherwise, and returns the response for
+    /// further assertions on success.
+    pub async fn assert_success<S, B, E>(self, app: &S, uri: &str) -> ServiceResponse<B>
+    where
+        S: Service<Request, Response = ServiceResponse<B>, Error = E>,
+        B: MessageBody,
+        E: std::fmt::Debug,
+    {
+        let (content_type, body) = self.build();
+        let req = TestRequest::post()
+            .uri(uri)
+            .insert_header(content_type)
+            .set_payload(body)
+            .to_request();
+
+        let resp = call_service(app, req).await;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = read_body(resp).await;
+            panic!(
+                "expected a 2xx response from {}, got {}: {}",
+                uri,
+                status,
+                String::from_utf8_lossy(&body)
+            );
+        }
+        resp
+    }
+
+    /// Build one independent single-part payload per part, each with its own boundary.
+    ///
+    /// Generated filenames and deferred fields are resolved against the full set of parts
+    /// before splitting.
+    /// Parts smuggled after the closing delimiter are not carried over.
+    pub fn build_per_part(self) -> Vec<((HeaderName, HeaderValue), Bytes)> {
+        self.resolved_parts()
+            .into_iter()
+            .map(|part| {
+                let mut builder = self.clone();
+                builder.boundary = Uuid::new_v4().to_string();
+                builder.parts = vec![part];
+                builder.filename_pattern = None;
+                builder.references.clear();
+                builder.deferred.clear();
+                builder.smuggled_parts.clear();
+                builder.build()
+            })
+            .collect()
+    }
+
+    /// Turn the builder into a closure that produces a fresh build on every call.
+    ///
+    /// Each call uses a new random boundary, so any boundary configured on the builder is
+    /// not retained.
+    pub fn into_factory(self) -> impl Fn() -> ((HeaderName, HeaderValue), Bytes) {
+        move || {
+            let mut builder = self.clone();
+            builder.boundary = Uuid::new_v4().to_string();
+            builder.build()
+        }
+    }
+
+    /// Build the payload framed with chunked transfer encoding, appending the chunk extension
+    /// `ext` (e.g. `name=value`) to every chunk-size line as `;name=value`. An empty `ext`
+    /// leaves the chunk-size lines bare.
+    ///
+    /// Returns the `Content-Type` and `Transfer-Encoding: chunked` headers alongside the
+    /// framed body.
+    pub fn build_chunked_with_extensions(
+        self,
+        ext: &str,
+    ) -> (Vec<(HeaderName, HeaderValue)>, Bytes) {
+        const CHUNK_SIZE: usize = 8 * 1024;
+
+        let (boundary, body) = self.serialize();
+        let ext = if ext.is_empty() {
+            String::new()
+        } else {
+            format!(";{}", ext)
+        };
+        let mut framed = BytesMut::new();
+        for chunk in body.chunks(CHUNK_SIZE) {
+            framed.extend_from_slice(format!("{:x}{}\r\n", chunk.len(), ext).as_bytes());
+            framed.extend_from_slice(chunk);
+            framed.extend_from_slice("\r\n".as_bytes());
+        }
+        framed.extend_from_slice(format!("0{}\r\n\r\n", ext).as_bytes());
+
+        let headers = vec![
+            (CONTENT_TYPE, content_type_value(&boundary)),
+            (TRANSFER_ENCODING, HeaderValue::from_static("chunked")),
+        ];
+
+        (headers, framed.freeze())
+    }
+
+    /// The most recently added part, for methods that decorate it.
+    fn last_part_mut(&mut self) -> &mut Part {
+        self.parts.last_mut().expect("no part has been added yet")
+    }
+
+    /// The parts as they will be serialized, with generated filenames filled in and the
+    /// deferred fields resolved and appended.
+    fn resolved_parts(&self) -> Vec<Part> {
+        let mut unnamed_files = 0;
+        let mut parts: Vec<Part> = self
+            .parts
+            .iter()
+            .cloned()
+            .map(|mut part| {
+                if let (Some(filename), Some(pattern)) = (&part.filename, &self.filename_pattern)
+                    && filename.is_empty()
+                {
+                    part.filename = Some(
+                        pattern
+                            .replace("{index}", &unnamed_files.to_string())
+                            .replace("{name}", &part.name),
+                    );
+                    unnamed_files += 1;
+                }
+                part
+            })
+            .collect();
+
+        let mut resolved: Vec<Option<Part>> = vec![None; self.deferred.len()];
+        for stage in 0..=2 {
+            for (index, field) in self.deferred.iter().enumerate() {
+                if field.stage() != stage {
+                    continue;
+                }
+                // every other part, with later-stage fields as placeholders, except that
+                // fields of the same stage don't cover each other
+                let others: Vec<Part> =
+                    parts
+                        .iter()
+                        .cloned()
+                        .chain(self.deferred.iter().enumerate().filter_map(
+                            |(other, other_field)| {
+                                if other == index || other_field.stage() == stage {
+                                    return None;
+                                }
+                                resolved[other]
+                                    .clone()
+                                    .or_else(|| other_field.placeholder())
+                            },
+                        ))
+                        .collect();
+                resolved[index] = Some(field.resolve(&others));
+            }
+        }
+        parts.extend(resolved.into_iter().flatten());
+
+        parts
+    }
+
+    /// The boundary used to serialize `parts`.
+    fn boundary_for(&self, parts: &[Part]) -> String {
+        if !self.content_derived_boundary {
+            return self.boundary.clone();
+        }
+
+        // 64-bit FNV-1a, which is stable across platforms and Rust versions. Only the regular
+        // parts are hashed, since build-time fields like timestamps change between builds.
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for byte in self.parts.iter().flat_map(|part| part.content.iter()) {
+            hash = (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+
+        let mut salt = 0u32;
+        loop {
+            let boundary = format!("content-{:016x}-{}", hash, salt);
+            let collides = parts.iter().any(|part| {
+                part.content
+                    .windows(boundary.len())
+                    .any(|window| window == boundary.as_bytes())
+            });
+            if !collides {
+                return boundary;
+            }
+            salt += 1;
+        }
+    }
+
+    /// The header lines of `part`, in emission order.
+    fn part_headers(&self, part: &Part) -> Vec<(String, String)> {
+        let mut order: Vec<PartHeaderKind> = Vec::new();
+        for kind in self.header_order.iter().chain(&DEFAULT_HEADER_ORDER) {
+            if !order.contains(kind) {
+                order.push(*kind);
+            }
+        }
+
+        let mut headers = Vec::new();
+        for kind in order {
+            match kind {
+                PartHeaderKind::ContentDisposition => {
+                    let quote = |value: &str| match part.param_style {
+                        ParamStyle::Escaped => format!("\"{}\"", escape_quoted(value)),
+                        ParamStyle::Verbatim => format!("\"{}\"", value),
+                        ParamStyle::SingleQuoted => format!("'{}'", value),
+                    };
+                    let mut disposition = format!("form-data; name={}", quote(&part.name));
+                    if let Some(filename) = &part.filename {
+                        disposition.push_str(&format!("; filename={}", quote(filename)));
+                        if part.param_style == ParamStyle::Escaped && !filename.is_ascii() {
+                            disposition.push_str(&format!(
+                                "; filename*=UTF-8''{}",
+                                percent_encode(filename)
+                            ));
+                        }
+                    }
+                    for (key, value) in &part.disposition_params {
+                        disposition.push_str(&format!("; {}=\"{}\"", key, escape_quoted(value)));
+                    }
+                    headers.push((self.header_case.apply("Content-Disposition"), disposition));
+                }
+                PartHeaderKind::ContentType => {
+                    if let Some(content_type) = &part.content_type {
+                        headers
+                            .push((self.header_case.apply("Content-Type"), content_type.clone()));
+                    }
+                }
+                PartHeaderKind::Custom => {
+                    if let Some(name) = &part.conflicting_name {
+                        headers.push((
+                            self.header_case.apply("Content-Disposition"),
+                            format!("form-data; name=\"{}\"", escape_quoted(name)),
+                        ));
+                    }
+                    headers.extend(part.headers.iter().cloned());
+                }
+            }
+        }
+        headers
+    }
+
+    /// Panic unless every reference field points to one of the resolved `parts`.
+    fn check_references(&self, parts: &[Part]) {
+        for target in &self.references {
+            assert!(
+                parts.iter().any(|part| &part.name == target),
+                "reference field points to missing part \"{target}\""
+            );
+        }
+    }
+
+    /// Resolve the parts once and serialize them, returning the boundary and the body.
+    ///
+    /// Deferred fields such as timestamps are only computed here, so the boundary always
+    /// matches the body it was derived for.
+    fn serialize(&self) -> (String, Bytes) {
+        self.serialize_parts(&self.resolved_parts())
+    }
+
+    /// Serialize already resolved `parts` into the multipart body, returning the boundary and
+    /// the body.
+    fn serialize_parts(&self, parts: &[Part]) -> (String, Bytes) {
+        self.check_references(parts);
+
+        let boundary = self.boundary_for(parts);
+        let delimiter = format!("{}{}", "-".repeat(self.delimiter_dashes), boundary);
+        let mut body = BytesMut::new();
+        if self.leading_bom {
+            body.extend_from_slice(b"\xef\xbb\xbf");
+        }
+
+        let write_part = |body: &mut BytesMut, part: &Part| {
+            body.extend_from_slice(format!("{}\r\n", delimiter).as_bytes());
+
+            let separator = self.header_spacing.separator();
+            for (name, value) in self.part_headers(part) {
+                body.extend_from_slice(format!("{}{}{}\r\n", name, separator, value).as_bytes());
+            }
+            body.extend_from_slice("\r\n".as_bytes());
+            body.extend_from_slice(&part.content);
+            if self.interpart_crlf || part.content.ends_with(b"\r\n") {
+                body.extend_from_slice("\r\n".as_bytes());
+            }
+        };
+
+        for part in parts {
+            write_part(&mut body, part);
+        }
+
+        match self.closing_style {
+            ClosingStyle::Correct => {
+                body.extend_from_slice(format!("{}--\r\n", delimiter).as_bytes())
+            }
+            ClosingStyle::MissingDashes => {
+                body.extend_from_slice(format!("{}\r\n", delimiter).as_bytes())
+            }
+            ClosingStyle::Missing => {}
+        }
+
+        if !self.smuggled_parts.is_empty() {
+            for part in &self.smuggled_parts {
+                write_part(&mut body, part);
+            }
+            body.extend_from_slice(format!("{}--\r\n", delimiter).as_bytes());
+        }
+
+        (boundary, body.freeze())
+    }
+}
+
+/// Escape a `Content-Disposition` parameter value for use inside double quotes.
+///
+/// `"` and `\` are backslash-escaped, while CR, LF and other control characters are
+/// percent-encoded so they can't terminate the header line. Non-ASCII is kept as UTF-8.
+fn escape_quoted(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c if c.is_control() => {
+                let mut buf = [0; 4];
+                for byte in c.encode_utf8(&mut buf).bytes() {
+                    escaped.push_str(&format!("%{:02X}", byte));
+                }
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Encode `value` using the `application/x-www-form-urlencoded` byte serializer.
+fn form_urlencode(value: &[u8]) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for &byte in value {
+        match byte {
+            b' ' => encoded.push('+'),
+            b'*' | b'-' | b'.' | b'_' => encoded.push(byte as char),
+            byte if byte.is_ascii_alphanumeric() => encoded.push(byte as char),
+            byte => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Percent-encode `value` as the UTF-8 `value-chars` of an RFC 8187 extended parameter.
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Whether `value` is a non-empty RFC 7230 `token`.
+fn is_token(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte))
+}
+
+/// The `multipart/form-data` content type for `boundary`.
+fn content_type_value(boundary: &str) -> HeaderValue {
+    HeaderValue::from_str(&format!(
+        "multipart/form-data; boundary={}",
+        boundary_param(boundary)
+    ))
+    .unwrap()
+}
+
+/// Characters RFC 2046 allows in a boundary, besides the space that may not end it.
+const BCHARS_NOSPACE: &str = "'()+_,-./:=?";
+
+/// Whether `boundary` only contains characters allowed by RFC 2046.
+fn is_valid_boundary(boundary: &str) -> bool {
+    (1..=70).contains(&boundary.len())
+        && !boundary.ends_with(' ')
+        && boundary
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == ' ' || BCHARS_NOSPACE.contains(c))
+}
+
+/// The `boundary` parameter value, quoted if it contains `tspecials` or spaces.
+fn boundary_param(boundary: &str) -> String {
+    debug_assert!(is_valid_boundary(boundary), "invalid boundary {boundary:?}");
+    if boundary.chars().any(|c| "()<>@,;:\\\"/[]?= ".contains(c)) {
+        format!("\"{}\"", boundary)
+    } else {
+        boundary.to_string()
+    }
+}
+
+/// Guess the content type of a file from its extension.
+fn content_type_for_path(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+
+    match extension.as_deref() {
+        Some("txt") => "text/plain",
+        Some("html" | "htm") => "text/html",
+        Some("css") => "text/css",
+        Some("csv") => "text/csv",
+        Some("js") => "text/javascript",
+        Some("json") => "application/json",
+        Some("xml") => "application/xml",
+        Some("pdf") => "application/pdf",
+        Some("zip") => "application/zip",
+        Some("gz") => "application/gzip",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("svg") => "image/svg+xml",
+        Some("mp3") => "audio/mpeg",
+        Some("wav") => "audio/wav",
+        Some("mp4") => "video/mp4",
+        Some("webm") => "video/webm",
+        _ => "application/octet-stream",
+    }
+}
+
+impl Default for TestMultipartBuilder {
+    
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    #[cfg(feature = "json")]
+    use actix_multipart::form::{
         json::Json as MpJson, tempfile::TempFile, MultipartForm, MultipartFormConfig,
     };
-    use actix_web::{App, Responder, post, test};
+    use actix_multipart::{Multipart, MultipartError};
+    #[cfg(feature = "json")]
+    use actix_web::Responder;
+    use actix_web::http::StatusCode;
+    use actix_web::http::header::{
+        CONTENT_LENGTH, CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue,
+    };
+    use actix_web::rt::time::timeout;
+    use actix_web::{App, HttpRequest, HttpResponse, post, test, web};
+    use bytes::Bytes;
+    use futures_util::{StreamExt as _, stream};
+    #[cfg(feature = "json")]
     use serde::{Deserialize, Serialize};
 
-    use super::TestMultipartBuilder;
+    use super::{
+        ClosingStyle, ExpectedPart, HeaderCase, HeaderSpacing, NameEncoding, PartHeaderKind,
+        TestMultipartBuilder,
+    };
+
+
+    #[cfg(feature = "json")]
+    #[derive(Debug, Deserialize, Serialize)]
+    struct Metadata {
+        name: String,
+    }
+
+    #[cfg(feature = "json")]
+    #[derive(Debug, MultipartForm)]
+    struct UploadForm {
+        // Note: the form is also subject to the global limits configured using `MultipartFormConfig`.
+        #[multipart(limit = "100MB")]
+        file: TempFile,
+        json: MpJson<Metadata>,
+    }
+
+    #[cfg(feature = "json")]
+    #[post("/videos")]
+    async fn post_video(MultipartForm(form): MultipartForm<UploadForm>) -> impl Responder {
+        format!(
+            "Uploaded file {}, with size: {}\ntemporary file ({}) was deleted\n",
+            form.json.name,
+            form.file.size,
+            form.file.file.path().display(),
+        )
+    }
+
+    /// A field as seen by `actix_multipart` after parsing a built payload.
+    struct ParsedField {
+        name: String,
+        filename: Option<String>,
+        content_type: Option<String>,
+        content: Bytes,
+    }
+
+    /// Run the output of `build()` through `actix_multipart`'s parser.
+    async fn parse(
+        ((_, content_type), body): ((HeaderName, HeaderValue), Bytes),
+    ) -> Result<Vec<ParsedField>, MultipartError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, content_type);
+
+        let mut multipart = Multipart::new(&headers, stream::once(async { Ok(body) }));
+        let mut fields = Vec::new();
+
+        while let Some(field) = multipart.next().await {
+            let mut field = field?;
+            let name = field.name().unwrap_or_default().to_string();
+            let filename = field
+                .content_disposition()
+                .and_then(|disposition| disposition.get_filename())
+                .map(str::to_string);
+            let content_type = field.content_type().map(ToString::to_string);
+            let content = field.bytes(usize::MAX).await.unwrap()?;
+
+            fields.push(ParsedField {
+                name,
+                filename,
+                content_type,
+                content,
+            });
+        }
+
+        Ok(fields)
+    }
+
+    #[post("/declared-length")]
+    async fn check_declared_length(mut payload: Multipart) -> HttpResponse {
+        while let Some(Ok(mut field)) = payload.next().await {
+            let declared = field
+                .headers()
+                .get("X-Upload-Length")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<usize>().ok());
+            let actual = field.bytes(usize::MAX).await.unwrap().unwrap().len();
+
+            if declared.is_some_and(|declared| declared != actual) {
+                return HttpResponse::BadRequest().body("declared length mismatch");
+            }
+        }
+        HttpResponse::Ok().finish()
+    }
+
+    /// Echoes `name: content type` per field, defaulting missing and wildcard types to
+    /// `application/octet-stream`.
+    #[post("/content-types")]
+    async fn echo_content_types(mut payload: Multipart) -> HttpResponse {
+        let mut lines = Vec::new();
+        while let Some(Ok(field)) = payload.next().await {
+            let content_type = match field.content_type() {
+                Some(mime) if mime.essence_str() != "*/*" => mime.to_string(),
+                _ => "application/octet-stream".to_string(),
+            };
+            lines.push(format!(
+                "{}: {}",
+                field.name().unwrap_or_default(),
+                content_type
+            ));
+        }
+        HttpResponse::Ok().body(lines.join("\n"))
+    }
+
+    #[cfg(feature = "json")]
+    #[actix_web::test]
+    async fn test_builder_against_handler() {
+        // Arrange
+        let app = test::init_service(
+            App::new()
+                .service(post_video)
+                .app_data(MultipartFormConfig::default().total_limit(100 * 1024 * 1024)),
+        )
+        .await;
+
+        let metadata = Metadata {
+            name: "MyTestVideo".to_string(),
+        };
+        let file_content = "This is a dummy video file".as_bytes();
+
+        let builder = TestMultipartBuilder::new()
+            .with_json("json", &metadata)
+            .with_bytes(
+                "file",
+                "test_video.mp4",
+                "video/mp4",
+                file_content,
+            );
+
+        let (content_type, body) = builder.build();
+
+        // Act
+        let req = test::TestRequest::post()
+            .uri("/videos")
+            .insert_header(content_type)
+            .set_payload(body)
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        // Assert
+        assert!(resp.status().is_success(), "Response was not 2xx");
+
+        let body_bytes = test::read_body(resp).await;
+        let body_str = String::from_utf8(body_bytes.to_vec()).unwrap();
+
+        assert!(body_str.contains("MyTestVideo"));
+        assert!(body_str.contains(&file_content.len().to_string()));
+        assert!(body_str.contains("was deleted"));
+    }
+
+    #[actix_web::test]
+    async fn test_declared_upload_length_mismatch() {
+        let app = test::init_service(App::new().service(check_declared_length)).await;
+
+        for (builder, expect_success) in [
+            (
+                TestMultipartBuilder::new()
+                    .with_bytes("file", "a.bin", "application/octet-stream", &b"12345"[..])
+                    .with_declared_upload_length("file"),
+                true,
+            ),
+            (
+                TestMultipartBuilder::new()
+                    .with_bytes("file", "a.bin", "application/octet-stream", &b"12345"[..])
+                    .with_declared_upload_length_as("file", 42),
+                false,
+            ),
+        ] {
+            let (content_type, body) = builder.build();
+            let req = test::TestRequest::post()
+                .uri("/declared-length")
+                .insert_header(content_type)
+                .set_payload(body)
+                .to_request();
+
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status().is_success(), expect_success);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_numeric_boundary() {
+        let builder = TestMultipartBuilder::new()
+            .with_numeric_boundary(40)
+            .with_text("greeting", "hello");
+        let boundary = builder.boundary.clone();
+        assert_eq!(boundary.len(), 40);
+        assert!(boundary.bytes().all(|byte| byte.is_ascii_digit()));
+
+        let ((_, content_type), body) = builder.build();
+        assert!(content_type.to_str().unwrap().ends_with(&boundary));
+        assert!(body.starts_with(format!("--{boundary}\r\n").as_bytes()));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, content_type);
+        let mut multipart = Multipart::new(&headers, stream::once(async { Ok(body) }));
+        let mut field = multipart.next().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("greeting"));
+        assert_eq!(field.bytes(usize::MAX).await.unwrap().unwrap(), "hello");
+    }
+
+    #[actix_web::test]
+    async fn test_raw_http_request() {
+        let builder = TestMultipartBuilder::new().with_text("greeting", "hello");
+        let raw = builder.to_raw_http_request("POST", "/upload", &[("X-Request-Id", "42")]);
+        let ((_, content_type), body) = builder.build();
+
+        let raw = std::str::from_utf8(&raw).unwrap();
+        let (head, raw_body) = raw.split_once("\r\n\r\n").unwrap();
+        let mut lines = head.split("\r\n");
+
+        assert_eq!(lines.next(), Some("POST /upload HTTP/1.1"));
+        assert!(head.contains(&format!("Content-Type: {}", content_type.to_str().unwrap())));
+        assert!(head.contains(&format!("Content-Length: {}", body.len())));
+        assert!(head.ends_with("X-Request-Id: 42"));
+        assert_eq!(raw_body.as_bytes(), &body[..]);
+    }
+
+    #[actix_web::test]
+    async fn test_group_by_kind() {
+        let builder = TestMultipartBuilder::new()
+            .with_bytes("first_file", "a.bin", "application/octet-stream", &b"a"[..])
+            .with_text("title", "hello")
+            .with_bytes(
+                "second_file",
+                "b.bin",
+                "application/octet-stream",
+                &b"b"[..],
+            )
+            .with_part(
+                "metadata".to_string(),
+                "application/json".to_string(),
+                None,
+                Bytes::from_static(b"{}"),
+            )
+            .group_by_kind();
+
+        let fields = parse(builder.build()).await.unwrap();
+        let names: Vec<_> = fields.iter().map(|field| field.name.as_str()).collect();
+
+        assert_eq!(names, ["title", "metadata", "first_file", "second_file"]);
+        assert_eq!(fields[0].content, "hello");
+        assert!(fields[..2].iter().all(|field| field.filename.is_none()));
+        assert!(fields[2..].iter().all(|field| field.filename.is_some()));
+    }
+
+    #[cfg(feature = "base64")]
+    #[actix_web::test]
+    async fn test_base64_content() {
+        let builder = TestMultipartBuilder::new()
+            .with_base64_content("file", "hello.txt", "text/plain", "aGVsbG8gd29ybGQ=")
+            .unwrap();
+
+        let fields = parse(builder.build()).await.unwrap();
+        assert_eq!(fields[0].filename.as_deref(), Some("hello.txt"));
+        assert_eq!(fields[0].content, "hello world");
+
+        assert!(
+            TestMultipartBuilder::new()
+                .with_base64_content("file", "hello.txt", "text/plain", "not base64!")
+                .is_err()
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_file_without_content_type() {
+        let app = test::init_service(App::new().service(echo_content_types)).await;
+
+        let builder =
+            TestMultipartBuilder::new().with_file_no_content_type("file", "blob", &b"data"[..]);
+        let (content_type, body) = builder.build();
+        assert!(!String::from_utf8_lossy(&body).contains("Content-Type"));
+
+        let req = test::TestRequest::post()
+            .uri("/content-types")
+            .insert_header(content_type)
+            .set_payload(body)
+            .to_request();
+        let resp = test::call_and_read_body(&app, req).await;
+
+        assert_eq!(resp, "file: application/octet-stream");
+    }
+
+    #[cfg(feature = "mime")]
+    #[actix_web::test]
+    async fn test_validate_content_types() {
+        let valid = TestMultipartBuilder::new()
+            .with_text("title", "hello")
+            .with_bytes("file", "a.png", "image/png", &b"png"[..]);
+        assert_eq!(valid.validate_content_types(), Ok(()));
+
+        let invalid = valid
+            .with_bytes("broken", "b.png", "image png", &b"png"[..])
+            .with_bytes("truncated", "c.bin", "application/", &b"bin"[..]);
+        assert_eq!(
+            invalid.validate_content_types(),
+            Err(vec![
+                ("broken".to_string(), "image png".to_string()),
+                ("truncated".to_string(), "application/".to_string()),
+            ])
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_filename_pattern() {
+        let builder = TestMultipartBuilder::new()
+            .with_bytes("file", "", "application/octet-stream", &b"0"[..])
+            .with_bytes("file", "", "application/octet-stream", &b"1"[..])
+            .with_bytes("named", "keep.bin", "application/octet-stream", &b"-"[..])
+            .with_bytes("file", "", "application/octet-stream", &b"2"[..])
+            .with_filename_pattern("upload_{index}.bin");
+
+        let fields = parse(builder.build()).await.unwrap();
+        let filenames: Vec<_> = fields
+            .iter()
+            .map(|field| field.filename.as_deref().unwrap())
+            .collect();
+
+        assert_eq!(
+            filenames,
+            ["upload_0.bin", "upload_1.bin", "keep.bin", "upload_2.bin"]
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_chunked_with_extensions() {
+        let builder = TestMultipartBuilder::new()
+            .with_text("title", "hello")
+            .with_bytes(
+                "file",
+                "big.bin",
+                "application/octet-stream",
+                vec![7u8; 20_000],
+            );
+        let (_, expected) = builder.serialize();
+
+        let (headers, framed) = builder.build_chunked_with_extensions("ext=val");
+        assert_eq!(headers[1].1, "chunked");
+
+        let mut decoded = Vec::new();
+        let mut rest = &framed[..];
+        loop {
+            let line_end = rest
+                .windows(2)
+                .position(|window| window == b"\r\n")
+                .unwrap();
+            let size_line = std::str::from_utf8(&rest[..line_end]).unwrap();
+            let (size, ext) = size_line.split_once(';').unwrap();
+            assert_eq!(ext, "ext=val");
+
+            let size = usize::from_str_radix(size, 16).unwrap();
+            rest = &rest[line_end + 2..];
+            if size == 0 {
+                assert_eq!(rest, b"\r\n");
+                break;
+            }
+            decoded.extend_from_slice(&rest[..size]);
+            assert_eq!(&rest[size..size + 2], b"\r\n");
+            rest = &rest[size + 2..];
+        }
+
+        assert_eq!(decoded, expected);
+    }
+
+    #[actix_web::test]
+    async fn test_chunked_with_empty_extension() {
+        let builder = TestMultipartBuilder::new().with_text("title", "hello");
+        let (_, expected) = builder.serialize();
+
+        let (_, framed) = builder.build_chunked_with_extensions("");
+        let mut framed_expected = format!("{:x}\r\n", expected.len()).into_bytes();
+        framed_expected.extend_from_slice(&expected);
+        framed_expected.extend_from_slice(b"\r\n0\r\n\r\n");
+
+        assert_eq!(framed, framed_expected);
+    }
+
+    #[actix_web::test]
+    async fn test_framing_overhead() {
+        let builder = TestMultipartBuilder::new()
+            .with_text("title", "hello")
+            .with_bytes("file", "a.bin", "application/octet-stream", &b"12345"[..]);
+        let boundary_len = builder.boundary.len();
+
+        assert_eq!(builder.total_content_size(), 10);
+        assert_eq!(
+            builder.framing_overhead(),
+            builder.byte_len() - builder.total_content_size()
+        );
+
+        let overhead = builder.framing_overhead();
+        let (_, body) = builder.build();
+        assert_eq!(body.len() - 10, overhead);
+
+        let empty = TestMultipartBuilder::new();
+        assert_eq!(empty.framing_overhead(), "----\r\n".len() + boundary_len);
+    }
+
+    #[actix_web::test]
+    async fn test_reference_field() {
+        let builder = TestMultipartBuilder::new()
+            .with_reference_field("cover", "image")
+            .with_bytes("image", "cover.png", "image/png", &b"png"[..]);
+
+        let fields = parse(builder.build()).await.unwrap();
+        assert_eq!(fields[0].name, "cover");
+        assert_eq!(fields[0].content, "image");
+        assert_eq!(fields[1].name, "image");
+    }
+
+    #[actix_web::test]
+    #[should_panic(expected = "reference field points to missing part \"image\"")]
+    async fn test_reference_field_missing_target() {
+        TestMultipartBuilder::new()
+            .with_reference_field("cover", "image")
+            .with_bytes("thumbnail", "thumb.png", "image/png", &b"png"[..])
+            .build();
+    }
+
+    #[actix_web::test]
+    async fn test_reference_field_to_build_time_field() {
+        let builder = TestMultipartBuilder::new()
+            .with_reference_field("sent_at_field", "sent_at")
+            .with_timestamp_field_at("sent_at", 1_700_000_000_000);
+
+        let fields = parse(builder.build()).await.unwrap();
+        assert_eq!(fields[0].content, "sent_at");
+        assert_eq!(fields[1].name, "sent_at");
+    }
+
+    #[actix_web::test]
+    #[should_panic(expected = "reference field points to missing part \"image\"")]
+    async fn test_reference_field_missing_target_urlencoded() {
+        TestMultipartBuilder::new()
+            .with_reference_field("cover", "image")
+            .build_urlencoded();
+    }
+
+    #[actix_web::test]
+    async fn test_into_factory() {
+        let factory = TestMultipartBuilder::new()
+            .with_text("title", "hello")
+            .with_bytes("file", "a.bin", "application/octet-stream", &b"12345"[..])
+            .into_factory();
+
+        let first = factory();
+        let second = factory();
+        assert_ne!(
+            first.0.1, second.0.1,
+            "each build should use a fresh boundary"
+        );
+
+        for build in [first, second] {
+            let fields = parse(build).await.unwrap();
+            assert_eq!(fields.len(), 2);
+            assert_eq!(fields[0].content, "hello");
+            assert_eq!(fields[1].content, "12345");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_untrimmed_content_type() {
+        let app = test::init_service(App::new().service(echo_content_types)).await;
+
+        let builder = TestMultipartBuilder::new().with_untrimmed_content_type(
+            "metadata",
+            " application/json ",
+            &b"{}"[..],
+        );
+        let (content_type, body) = builder.build();
+        assert!(String::from_utf8_lossy(&body).contains("Content-Type:  application/json \r\n"));
+
+        let req = test::TestRequest::post()
+            .uri("/content-types")
+            .insert_header(content_type)
+            .set_payload(body)
+            .to_request();
+        let resp = test::call_and_read_body(&app, req).await;
+
+        // actix trims the header value, so the type is recognized as JSON
+        assert_eq!(resp, "metadata: application/json");
+    }
+
+    #[actix_web::test]
+    async fn test_browser_defaults() {
+        let builder = TestMultipartBuilder::new()
+            .with_text("title", "hello")
+            .with_browser_defaults()
+            .with_bytes("file", "a.bin", "application/octet-stream", &b"12345"[..]);
+
+        let fields = parse(builder.build()).await.unwrap();
+        let names: Vec<_> = fields.iter().map(|field| field.name.as_str()).collect();
+
+        assert_eq!(names, ["title", "_charset_", "file"]);
+        assert_eq!(fields[1].content, "UTF-8");
+        assert_eq!(fields[1].content_type, None);
+    }
+
+    #[actix_web::test]
+    async fn test_lowercase_header_case() {
+        let builder = TestMultipartBuilder::new()
+            .with_header_case(HeaderCase::Lowercase)
+            .with_text("title", "hello")
+            .with_bytes("file", "a.png", "image/png", &b"png"[..]);
+        let (content_type, body) = builder.build();
+
+        let raw = String::from_utf8_lossy(&body);
+        assert!(raw.contains("content-disposition: form-data; name=\"title\""));
+        assert!(raw.contains("content-type: image/png"));
+        assert!(!raw.contains("Content-"));
+
+        let fields = parse((content_type, body)).await.unwrap();
+        assert_eq!(fields[0].name, "title");
+        assert_eq!(fields[0].content, "hello");
+        assert_eq!(fields[1].filename.as_deref(), Some("a.png"));
+        assert_eq!(fields[1].content_type.as_deref(), Some("image/png"));
+    }
+
+    #[cfg(feature = "sha2")]
+    #[post("/checksum")]
+    async fn verify_checksum(mut payload: Multipart) -> HttpResponse {
+        use sha2::{Digest, Sha256};
+
+        let mut hasher = Sha256::new();
+        let mut expected = None;
+        while let Some(Ok(mut field)) = payload.next().await {
+            let content = field.bytes(usize::MAX).await.unwrap().unwrap();
+            if field.name() == Some("checksum") {
+                expected = Some(content);
+            } else {
+                hasher.update(&content);
+            }
+        }
+
+        if expected.as_deref() == Some(format!("{:x}", hasher.finalize()).as_bytes()) {
+            HttpResponse::Ok().finish()
+        } else {
+            HttpResponse::BadRequest().finish()
+        }
+    }
+
+    #[cfg(feature = "sha2")]
+    #[actix_web::test]
+    async fn test_aggregate_checksum_field() {
+        let app = test::init_service(App::new().service(verify_checksum)).await;
+
+        let builder = TestMultipartBuilder::new()
+            .with_text("title", "hello")
+            .with_aggregate_checksum_field("checksum")
+            .with_bytes("file", "a.bin", "application/octet-stream", &b"12345"[..]);
+        let (content_type, body) = builder.build();
+
+        let req = test::TestRequest::post()
+            .uri("/checksum")
+            .insert_header(content_type)
+            .set_payload(body)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert!(resp.status().is_success());
+    }
+
+    #[cfg(all(feature = "sha2", feature = "json"))]
+    #[actix_web::test]
+    async fn test_aggregate_checksum_covers_manifest() {
+        let app = test::init_service(App::new().service(verify_checksum)).await;
+
+        for builder in [
+            TestMultipartBuilder::new()
+                .with_text("title", "hello")
+                .with_aggregate_checksum_field("checksum")
+                .with_manifest_field("manifest"),
+            TestMultipartBuilder::new()
+                .with_text("title", "hello")
+                .with_manifest_field("manifest")
+                .with_timestamp_field_at("sent_at", 1_700_000_000_000)
+                .with_aggregate_checksum_field("checksum"),
+        ] {
+            let fields = parse(builder.clone().build()).await.unwrap();
+            let manifest = fields
+                .iter()
+                .find(|field| field.name == "manifest")
+                .unwrap();
+            let manifest: Vec<ManifestEntry> = serde_json::from_slice(&manifest.content).unwrap();
+            let checksum = manifest
+                .iter()
+                .find(|entry| entry.name == "checksum")
+                .unwrap();
+            assert_eq!(checksum.content_type.as_deref(), Some("text/plain"));
+            assert_eq!(checksum.length, 64);
+
+            let (content_type, body) = builder.build();
+            let req = test::TestRequest::post()
+                .uri("/checksum")
+                .insert_header(content_type)
+                .set_payload(body)
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+
+            assert!(resp.status().is_success());
+        }
+    }
+
+    #[post("/protected")]
+    async fn protected_upload(req: HttpRequest, mut payload: Multipart) -> HttpResponse {
+        if req
+            .headers()
+            .get("Authorization")
+            .map(|value| value.as_bytes())
+            != Some(b"Bearer secret-token")
+        {
+            return HttpResponse::Unauthorized().finish();
+        }
+
+        let mut fields = 0;
+        while let Some(Ok(_)) = payload.next().await {
+            fields += 1;
+        }
+        HttpResponse::Ok().body(fields.to_string())
+    }
+
+    #[actix_web::test]
+    async fn test_authed_request() {
+        let app = test::init_service(App::new().service(protected_upload)).await;
+        let builder = TestMultipartBuilder::new().with_text("title", "hello");
+
+        let req = builder
+            .clone()
+            .into_authed_request("secret-token")
+            .uri("/protected")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+        assert_eq!(test::read_body(resp).await, "1");
+
+        let req = builder
+            .into_authed_request("wrong-token")
+            .uri("/protected")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn test_pin_first() {
+        let builder = TestMultipartBuilder::new()
+            .with_text("title", "hello")
+            .with_bytes("file", "a.bin", "application/octet-stream", &b"12345"[..])
+            .with_text("csrf_token", "first")
+            .with_text("csrf_token", "second")
+            .pin_first("csrf_token");
+
+        let fields = parse(builder.build()).await.unwrap();
+        let names: Vec<_> = fields.iter().map(|field| field.name.as_str()).collect();
+
+        assert_eq!(names, ["csrf_token", "title", "file", "csrf_token"]);
+        assert_eq!(fields[0].content, "first");
+    }
+
+    #[cfg(feature = "json")]
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct ManifestEntry {
+        name: String,
+        content_type: Option<String>,
+        filename: Option<String>,
+        length: usize,
+    }
+
+    #[cfg(feature = "json")]
+    #[actix_web::test]
+    async fn test_manifest_field() {
+        let builder = TestMultipartBuilder::new()
+            .with_text("title", "hello")
+            .with_manifest_field("manifest")
+            .with_bytes("file", "a.bin", "application/octet-stream", &b"12345"[..])
+            .with_timestamp_field_at("sent_at", 1);
+
+        let fields = parse(builder.build()).await.unwrap();
+        let names: Vec<_> = fields.iter().map(|field| field.name.as_str()).collect();
+        assert_eq!(names, ["title", "file", "manifest", "sent_at"]);
+
+        let manifest: Vec<ManifestEntry> = serde_json::from_slice(&fields[2].content).unwrap();
+        let expected: Vec<_> = fields
+            .iter()
+            .filter(|field| field.name != "manifest")
+            .map(|field| ManifestEntry {
+                name: field.name.clone(),
+                content_type: field.content_type.clone(),
+                filename: field.filename.clone(),
+                length: field.content.len(),
+            })
+            .collect();
+
+        assert_eq!(manifest, expected);
+    }
+
+    #[actix_web::test]
+    async fn test_malformed_closing() {
+        for (style, expect_ok) in [
+            (ClosingStyle::Correct, true),
+            (ClosingStyle::MissingDashes, false),
+            (ClosingStyle::Missing, false),
+        ] {
+            let builder = TestMultipartBuilder::new()
+                .with_text("title", "hello")
+                .with_malformed_closing(style);
+
+            // without any closing delimiter actix waits for more data instead of erroring
+            let result = timeout(Duration::from_secs(1), parse(builder.build())).await;
+            let accepted = matches!(result, Ok(Ok(_)));
+            assert_eq!(accepted, expect_ok, "unexpected result for {style:?}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_mp4_fixture() {
+        let builder = TestMultipartBuilder::new().with_test_mp4("video", "clip.mp4");
+
+        let fields = parse(builder.build()).await.unwrap();
+        let content = &fields[0].content;
+
+        let box_size = u32::from_be_bytes(content[..4].try_into().unwrap()) as usize;
+        assert_eq!(box_size, content.len());
+        assert_eq!(&content[4..8], b"ftyp");
+        assert_eq!(fields[0].content_type.as_deref(), Some("video/mp4"));
+        assert_eq!(infer::get(content).unwrap().mime_type(), "video/mp4");
+    }
+
+    #[actix_web::test]
+    async fn test_conflicting_disposition() {
+        let builder =
+            TestMultipartBuilder::new().with_conflicting_disposition("real", "fake", "hello");
+        let (content_type, body) = builder.build();
+
+        let raw = String::from_utf8_lossy(&body);
+        assert!(raw.contains("name=\"real\"\r\nContent-Type: text/plain\r\n"));
+        assert!(raw.contains("Content-Disposition: form-data; name=\"fake\"\r\n"));
+
+        // actix resolves the first Content-Disposition header
+        let fields = parse((content_type, body)).await.unwrap();
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0].name, "real");
+        assert_eq!(fields[0].content, "hello");
+
+        let (_, body) = TestMultipartBuilder::new()
+            .with_header_case(HeaderCase::Lowercase)
+            .with_conflicting_disposition("real", "fake\"\r\nX-Injected: 1", "hello")
+            .build();
+        let raw = String::from_utf8_lossy(&body);
+        assert!(
+            raw.contains(
+                "content-disposition: form-data; name=\"fake\\\"%0D%0AX-Injected: 1\"\r\n"
+            )
+        );
+        assert!(!raw.contains("\r\nX-Injected"));
+    }
+
+    #[cfg(feature = "json")]
+    #[actix_web::test]
+    async fn test_assert_success() {
+        let app = test::init_service(App::new().service(post_video)).await;
+
+        let resp = TestMultipartBuilder::new()
+            .with_json(
+                "json",
+                &Metadata {
+                    name: "MyTestVideo".to_string(),
+                },
+            )
+            .with_bytes("file", "test_video.mp4", "video/mp4", &b"video"[..])
+            .assert_success(&app, "/videos")
+            .await;
+
+        let body = test::read_body(resp).await;
+        assert!(String::from_utf8_lossy(&body).contains("MyTestVideo"));
+    }
+
+    #[actix_web::test]
+    #[should_panic(expected = "got 400 Bad Request: declared length mismatch")]
+    async fn test_assert_success_reports_body() {
+        let app = test::init_service(App::new().service(check_declared_length)).await;
+
+        TestMultipartBuilder::new()
+            .with_bytes("file", "a.bin", "application/octet-stream", &b"12345"[..])
+            .with_declared_upload_length_as("file", 42)
+            .assert_success(&app, "/declared-length")
+            .await;
+    }
+
+    /// Echoes the uploaded filename reduced to its final path component.
+    #[post("/sanitize")]
+    async fn sanitize_filename(mut payload: Multipart) -> HttpResponse {
+        let Some(Ok(field)) = payload.next().await else {
+            return HttpResponse::BadRequest().finish();
+        };
+        let raw = field
+            .content_disposition()
+            .and_then(|disposition| disposition.get_filename())
+            .unwrap_or_default();
+        let sanitized = raw
+            .rsplit(['/', '\\'])
+            .next()
+            .filter(|name| !name.is_empty() && *name != "..")
+            .unwrap_or("upload");
+
+        HttpResponse::Ok().body(format!("{raw} -> {sanitized}"))
+    }
+
+    #[actix_web::test]
+    async fn test_traversal_filename() {
+        let app = test::init_service(App::new().service(sanitize_filename)).await;
+
+        for (filename, expected) in [
+            ("../../etc/passwd", "../../etc/passwd -> passwd"),
+            // actix reads `\.` inside the quoted filename as an escaped `.`
+            ("..\\..\\evil.exe", "....evil.exe -> ....evil.exe"),
+        ] {
+            let builder = TestMultipartBuilder::new().with_traversal_filename(
+                "file",
+                filename,
+                "text/plain",
+                "root:x:0:0",
+            );
+            let (content_type, body) = builder.build();
+            assert!(String::from_utf8_lossy(&body).contains(&format!("filename=\"{filename}\"")));
+
+            let req = test::TestRequest::post()
+                .uri("/sanitize")
+                .insert_header(content_type)
+                .set_payload(body)
+                .to_request();
+            let resp = test::call_and_read_body(&app, req).await;
+
+            assert_eq!(resp, expected);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_delimiter_dash_count() {
+        for (count, expect_ok) in [(2, true), (1, false)] {
+            let builder = TestMultipartBuilder::new()
+                .with_text("title", "hello")
+                .with_delimiter_dash_count(count);
+            let boundary = builder.boundary.clone();
+            let (content_type, body) = builder.build();
+
+            assert!(
+                content_type
+                    .1
+                    .to_str()
+                    .unwrap()
+                    .ends_with(&format!("={boundary}"))
+            );
+            assert!(body.starts_with(format!("{}{boundary}\r\n", "-".repeat(count)).as_bytes()));
+
+            let result = timeout(Duration::from_secs(1), parse((content_type, body))).await;
+            let accepted = matches!(result, Ok(Ok(_)));
+            assert_eq!(accepted, expect_ok, "unexpected result for {count} dashes");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_content_derived_boundary() {
+        let build = || {
+            TestMultipartBuilder::new()
+                .with_text("title", "hello")
+                .with_bytes("file", "a.bin", "application/octet-stream", &b"12345"[..])
+                .with_content_derived_boundary()
+                .build()
+        };
+
+        let first = build();
+        let second = build();
+        assert_eq!(first, second);
+
+        let boundary = first
+            .0
+            .1
+            .to_str()
+            .unwrap()
+            .split_once("boundary=")
+            .unwrap()
+            .1
+            .to_string();
+        assert!(first.1.starts_with(format!("--{boundary}\r\n").as_bytes()));
+
+        let fields = parse(first).await.unwrap();
+        assert_eq!(fields.len(), 2);
+        assert!(
+            fields
+                .iter()
+                .all(|field| { !String::from_utf8_lossy(&field.content).contains(&boundary) })
+        );
+    }
+
+    /// Reassembles the uploaded chunks by their `X-Chunk-Index` and echoes the result.
+    #[post("/chunks")]
+    async fn reassemble_chunks(mut payload: Multipart) -> HttpResponse {
+        let header = |field: &actix_multipart::Field, name: &str| -> Option<usize> {
+            field.headers().get(name)?.to_str().ok()?.parse().ok()
+        };
+
+        let mut chunks = Vec::new();
+        let mut total = None;
+        while let Some(Ok(mut field)) = payload.next().await {
+            let (Some(index), Some(field_total)) = (
+                header(&field, "X-Chunk-Index"),
+                header(&field, "X-Total-Chunks"),
+            ) else {
+                return HttpResponse::BadRequest().body("missing chunk metadata");
+            };
+            total = Some(field_total);
+            chunks.push((index, field.bytes(usize::MAX).await.unwrap().unwrap()));
+        }
+
+        if total != Some(chunks.len()) {
+            return HttpResponse::BadRequest().body("missing chunks");
+        }
+        chunks.sort_by_key(|(index, _)| *index);
+        let file: Vec<u8> = chunks.into_iter().flat_map(|(_, chunk)| chunk).collect();
+        HttpResponse::Ok().body(file)
+    }
+
+    #[actix_web::test]
+    async fn test_chunk_metadata() {
+        let app = test::init_service(App::new().service(reassemble_chunks)).await;
+        let file = b"the quick brown fox jumps over the lazy dog";
+        let chunks: Vec<_> = file.chunks(10).collect();
+
+        // send the chunks out of order to exercise the index header
+        let mut builder = TestMultipartBuilder::new();
+        for index in (0..chunks.len()).rev() {
+            builder = builder
+                .with_bytes(
+                    "chunk",
+                    "fox.txt",
+                    "application/octet-stream",
+                    chunks[index],
+                )
+                .with_chunk_metadata(index, chunks.len());
+        }
+        let (content_type, body) = builder.build();
+
+        let req = test::TestRequest::post()
+            .uri("/chunks")
+            .insert_header(content_type)
+            .set_payload(body)
+            .to_request();
+        let resp = test::call_and_read_body(&app, req).await;
+
+        assert_eq!(resp, &file[..]);
+    }
+
+    #[actix_web::test]
+    async fn test_wildcard_content_type() {
+        let app = test::init_service(App::new().service(echo_content_types)).await;
+
+        let builder = TestMultipartBuilder::new().with_wildcard_content_type_part(
+            "file",
+            "unknown.dat",
+            &b"data"[..],
+        );
+        let (content_type, body) = builder.build();
+        assert!(String::from_utf8_lossy(&body).contains("Content-Type: */*\r\n"));
+
+        let req = test::TestRequest::post()
+            .uri("/content-types")
+            .insert_header(content_type)
+            .set_payload(body)
+            .to_request();
+        let resp = test::call_and_read_body(&app, req).await;
+
+        assert_eq!(resp, "file: application/octet-stream");
+    }
+
+    #[actix_web::test]
+    async fn test_header_order() {
+        let builder = TestMultipartBuilder::new()
+            .with_header_order(&[PartHeaderKind::Custom, PartHeaderKind::ContentType])
+            .with_bytes("file", "a.bin", "application/octet-stream", &b"12345"[..])
+            .with_declared_upload_length("file");
+        let (content_type, body) = builder.build();
+
+        let raw = String::from_utf8_lossy(&body);
+        let (_, rest) = raw.split_once("\r\n").unwrap();
+        let (head, _) = rest.split_once("\r\n\r\n").unwrap();
+        let lines: Vec<_> = head.split("\r\n").collect();
+        assert_eq!(
+            lines,
+            [
+                "X-Upload-Length: 5",
+                "Content-Type: application/octet-stream",
+                "Content-Disposition: form-data; name=\"file\"; filename=\"a.bin\"",
+            ]
+        );
+
+        let fields = parse((content_type, body)).await.unwrap();
+        assert_eq!(fields[0].filename.as_deref(), Some("a.bin"));
+        assert_eq!(fields[0].content, "12345");
+    }
+
+    #[actix_web::test]
+    async fn test_raw_name_field_with_null_byte() {
+        let verbatim = TestMultipartBuilder::new().with_raw_name_field(
+            "user\0admin",
+            "hello",
+            NameEncoding::Verbatim,
+        );
+        let (_, body) = verbatim.clone().build();
+        assert!(body.windows(11).any(|window| window == b"user\0admin\""));
+
+        // actix rejects the null byte in the part header, consistently
+        for _ in 0..2 {
+            assert!(parse(verbatim.clone().build()).await.is_err());
+        }
+
+        let encoded = TestMultipartBuilder::new().with_raw_name_field(
+            "user\0admin",
+            "hello",
+            NameEncoding::PercentEncoded,
+        );
+        let fields = parse(encoded.build()).await.unwrap();
+        assert_eq!(fields[0].name, "user%00admin");
+        assert_eq!(fields[0].content, "hello");
+    }
+
+    /// Accepts only fields that start with the PNG signature.
+    #[post("/png")]
+    async fn require_png(mut payload: Multipart) -> HttpResponse {
+        const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+        while let Some(Ok(mut field)) = payload.next().await {
+            let declared = field.content_type().map(ToString::to_string);
+            let content = field.bytes(usize::MAX).await.unwrap().unwrap();
+            if declared.as_deref() != Some("image/png") || !content.starts_with(PNG_SIGNATURE) {
+                return HttpResponse::UnprocessableEntity().body(format!(
+                    "{:?} with {} bytes is not a PNG",
+                    declared,
+                    content.len()
+                ));
+            }
+        }
+        HttpResponse::Ok().finish()
+    }
+
+    #[actix_web::test]
+    async fn test_empty_file_keeps_content_type() {
+        let builder =
+            TestMultipartBuilder::new().with_empty_file("avatar", "avatar.png", "image/png");
+
+        let fields = parse(builder.clone().build()).await.unwrap();
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0].filename.as_deref(), Some("avatar.png"));
+        assert_eq!(fields[0].content_type.as_deref(), Some("image/png"));
+        assert!(fields[0].content.is_empty());
+
+        let app = test::init_service(App::new().service(require_png)).await;
+        let (content_type, body) = builder.build();
+        let req = test::TestRequest::post()
+            .uri("/png")
+            .insert_header(content_type)
+            .set_payload(body)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            test::read_body(resp).await,
+            "Some(\"image/png\") with 0 bytes is not a PNG"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_content_type_map() {
+        let builder = TestMultipartBuilder::new()
+            .with_text("title", "hello")
+            .with_bytes("file", "a.png", "image/png", &b"png"[..])
+            .with_bytes("file", "b.png", "image/png", &b"png"[..])
+            .with_file_no_content_type("file", "c.bin", &b"bin"[..]);
+
+        let map = builder.content_type_map();
+
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["title"], ["text/plain"]);
+        assert_eq!(map["file"], ["image/png", "image/png"]);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[actix_web::test]
+    async fn test_mmap_file() {
+        let path = std::env::temp_dir().join(format!("{}.mp4", uuid::Uuid::new_v4()));
+        let content: Vec<u8> = (0..4 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &content).unwrap();
+
+        let builder = TestMultipartBuilder::new()
+            .with_mmap_file("video", &path)
+            .unwrap();
+        let result = parse(builder.build()).await;
+        std::fs::remove_file(&path).unwrap();
+
+        let fields = result.unwrap();
+        assert_eq!(fields[0].content.len(), content.len());
+        assert_eq!(fields[0].content, content);
+        assert_eq!(fields[0].content_type.as_deref(), Some("video/mp4"));
+        assert_eq!(
+            fields[0].filename.as_deref(),
+            path.file_name().and_then(|name| name.to_str())
+        );
+    }
+
+    /// Echoes `size content-type` of the first field.
+    #[post("/file-info")]
+    async fn file_info(mut payload: Multipart) -> HttpResponse {
+        let Some(Ok(mut field)) = payload.next().await else {
+            return HttpResponse::BadRequest().finish();
+        };
+        let content_type = field
+            .content_type()
+            .map(ToString::to_string)
+            .unwrap_or_default();
+        let content = field.bytes(usize::MAX).await.unwrap().unwrap();
+        HttpResponse::Ok().body(format!("{} {}", content.len(), content_type))
+    }
+
+    #[actix_web::test]
+    async fn test_file() {
+        let app = test::init_service(App::new().service(file_info)).await;
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/report.pdf");
+        let size = std::fs::metadata(path).unwrap().len();
+
+        let builder = TestMultipartBuilder::new().with_file_unwrap("report", path);
+        let (content_type, body) = builder.build();
+        assert!(String::from_utf8_lossy(&body).contains("filename=\"report.pdf\""));
+
+        let req = test::TestRequest::post()
+            .uri("/file-info")
+            .insert_header(content_type)
+            .set_payload(body)
+            .to_request();
+        let resp = test::call_and_read_body(&app, req).await;
+
+        assert_eq!(resp, format!("{size} application/pdf"));
+    }
+
+    #[actix_web::test]
+    async fn test_file_missing() {
+        let err = TestMultipartBuilder::new()
+            .with_file("report", "does/not/exist.pdf")
+            .err()
+            .unwrap();
+
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        assert!(err.to_string().starts_with("does/not/exist.pdf: "));
+    }
+
+    #[actix_web::test]
+    async fn test_header_spacing() {
+        for (style, expected) in [
+            (HeaderSpacing::NoSpace, "Content-Type:image/png\r\n"),
+            (HeaderSpacing::ExtraSpace, "Content-Type:   image/png\r\n"),
+        ] {
+            let builder = TestMultipartBuilder::new()
+                .with_header_spacing(style)
+                .with_text("title", "hello")
+                .with_bytes("file", "a.png", "image/png", &b"png"[..]);
+            let (content_type, body) = builder.build();
+            assert!(String::from_utf8_lossy(&body).contains(expected));
+
+            let fields = parse((content_type, body)).await.unwrap();
+            assert_eq!(fields[0].name, "title");
+            assert_eq!(fields[0].content, "hello");
+            assert_eq!(fields[1].filename.as_deref(), Some("a.png"));
+            assert_eq!(fields[1].content_type.as_deref(), Some("image/png"));
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_near_boundary_content() {
+        let builder = TestMultipartBuilder::new()
+            .with_near_boundary_content("tricky")
+            .with_text("after", "still here");
+        let prefix = builder.boundary[..builder.boundary.len() - 1].to_string();
+
+        let fields = parse(builder.build()).await.unwrap();
+
+        assert_eq!(fields.len(), 2);
+        assert_eq!(
+            fields[0].content,
+            format!("start\r\n--{prefix}\r\nmiddle --{prefix}-- end")
+        );
+        assert_eq!(fields[1].content, "still here");
+    }
+
+    #[actix_web::test]
+    async fn test_build_per_part() {
+        let builds = TestMultipartBuilder::new()
+            .with_text("title", "hello")
+            .with_bytes("file", "a.bin", "application/octet-stream", &b"12345"[..])
+            .build_per_part();
+
+        assert_eq!(builds.len(), 2);
+        assert_ne!(builds[0].0, builds[1].0);
+
+        let mut received = Vec::new();
+        for build in builds {
+            let fields = parse(build).await.unwrap();
+            assert_eq!(fields.len(), 1);
+            received.push((fields[0].name.clone(), fields[0].content.clone()));
+        }
+        assert_eq!(
+            received,
+            [
+                ("title".to_string(), Bytes::from("hello")),
+                ("file".to_string(), Bytes::from("12345")),
+            ]
+        );
+    }
+
+    /// Collects `items[<index>]` fields into an array and echoes it comma-separated.
+    #[post("/items")]
+    async fn collect_items(mut payload: Multipart) -> HttpResponse {
+        let mut items = Vec::new();
+        while let Some(Ok(mut field)) = payload.next().await {
+            let index = field
+                .name()
+                .and_then(|name| name.strip_prefix("items["))
+                .and_then(|rest| rest.strip_suffix(']'))
+                .and_then(|index| index.parse::<usize>().ok());
+            let content = field.bytes(usize::MAX).await.unwrap().unwrap();
+            if let Some(index) = index {
+                if items.len() <= index {
+                    items.resize(index + 1, String::new());
+                }
+                items[index] = String::from_utf8_lossy(&content).into_owned();
+            }
+        }
+        HttpResponse::Ok().body(items.join(","))
+    }
+
+    #[actix_web::test]
+    async fn test_indexed_field() {
+        let app = test::init_service(App::new().service(collect_items)).await;
+
+        let builder = TestMultipartBuilder::new()
+            .with_indexed_field("items", Some(1), "second")
+            .with_indexed_field("items", Some(0), "first")
+            .with_indexed_field("tags", None, "ignored");
+        let (content_type, body) = builder.build();
+        let raw = String::from_utf8_lossy(&body);
+        assert!(raw.contains("name=\"items[1]\""));
+        assert!(raw.contains("name=\"tags[]\""));
+
+        let req = test::TestRequest::post()
+            .uri("/items")
+            .insert_header(content_type)
+            .set_payload(body)
+            .to_request();
+        let resp = test::call_and_read_body(&app, req).await;
+
+        assert_eq!(resp, "first,second");
+    }
+
+    /// Reassembles parts carrying `Content-Range` headers and echoes the result.
+    #[post("/ranges")]
+    async fn reassemble_ranges(mut payload: Multipart) -> HttpResponse {
+        let mut file = Vec::new();
+        while let Some(Ok(mut field)) = payload.next().await {
+            let range = field
+                .headers()
+                .get("Content-Range")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("bytes "))
+                .and_then(|value| {
+                    let (range, total) = value.split_once('/')?;
+                    let (start, end) = range.split_once('-')?;
+                    Some((start.parse().ok()?, end.parse().ok()?, total.parse().ok()?))
+                });
+            let Some((start, end, total)) = range else {
+                return HttpResponse::BadRequest().body("invalid Content-Range");
+            };
+            let content = field.bytes(usize::MAX).await.unwrap().unwrap();
+            if content.len() != end - start + 1 {
+                return HttpResponse::BadRequest().body("range length mismatch");
+            }
+            file.resize(total, 0);
+            file[start..=end].copy_from_slice(&content);
+        }
+        HttpResponse::Ok().body(file)
+    }
+
+    #[actix_web::test]
+    async fn test_content_range() {
+        let app = test::init_service(App::new().service(reassemble_ranges)).await;
+        let file = b"the quick brown fox jumps over the lazy dog";
+        let total = file.len() as u64;
+
+        let builder = TestMultipartBuilder::new()
+            .with_bytes("chunk", "fox.txt", "text/plain", &file[20..])
+            .with_content_range(20, total - 1, total)
+            .with_bytes("chunk", "fox.txt", "text/plain", &file[..20])
+            .with_content_range(0, 19, total);
+        let (content_type, body) = builder.build();
+
+        let req = test::TestRequest::post()
+            .uri("/ranges")
+            .insert_header(content_type)
+            .set_payload(body)
+            .to_request();
+        let resp = test::call_and_read_body(&app, req).await;
+
+        assert_eq!(resp, &file[..]);
+    }
+
+    #[actix_web::test]
+    #[should_panic(expected = "invalid content range 0-10/10")]
+    async fn test_content_range_out_of_bounds() {
+        TestMultipartBuilder::new()
+            .with_text("chunk", "0123456789")
+            .with_content_range(0, 10, 10);
+    }
+
+    #[actix_web::test]
+    async fn test_nested_typed() {
+        let inner = TestMultipartBuilder::new()
+            .with_text("plain", "hello")
+            .with_part(
+                "html".to_string(),
+                "text/html".to_string(),
+                None,
+                Bytes::from_static(b"<p>hello</p>"),
+            );
+        let inner_boundary = inner.boundary.clone();
+        let (_, inner_body) = inner.serialize();
+        let builder = TestMultipartBuilder::new()
+            .with_text("subject", "greeting")
+            .with_nested_typed("body", "alternative", inner);
+        let (content_type, body) = builder.build();
+
+        let nested_type = format!("multipart/alternative; boundary={inner_boundary}");
+        let raw = String::from_utf8_lossy(&body);
+        assert!(raw.contains(&format!("Content-Type: {nested_type}\r\n\r\n")));
+        assert!(
+            body.windows(inner_body.len())
+                .any(|window| window == inner_body)
+        );
+
+        // actix-multipart refuses nested multipart parts outright
+        assert!(matches!(
+            parse((content_type, body)).await,
+            Err(MultipartError::Nested)
+        ));
+
+        let inner_fields = parse((
+            (CONTENT_TYPE, HeaderValue::from_str(&nested_type).unwrap()),
+            inner_body,
+        ))
+        .await
+        .unwrap();
+        assert_eq!(inner_fields.len(), 2);
+        assert_eq!(inner_fields[0].content, "hello");
+        assert_eq!(inner_fields[1].content_type.as_deref(), Some("text/html"));
+        assert_eq!(inner_fields[1].content, "<p>hello</p>");
+    }
+
+    /// Echoes the upload latency computed from the `sent_at` field.
+    #[post("/latency")]
+    async fn upload_latency(mut payload: Multipart) -> HttpResponse {
+        const RECEIVED_AT: u64 = 1_700_000_000_250;
+
+        while let Some(Ok(mut field)) = payload.next().await {
+            let content = field.bytes(usize::MAX).await.unwrap().unwrap();
+            if field.name() == Some("sent_at") {
+                let sent_at: u64 = std::str::from_utf8(&content).unwrap().parse().unwrap();
+                return HttpResponse::Ok().body((RECEIVED_AT - sent_at).to_string());
+            }
+        }
+        HttpResponse::BadRequest().finish()
+    }
+
+    #[actix_web::test]
+    async fn test_timestamp_field() {
+        let app = test::init_service(App::new().service(upload_latency)).await;
+
+        let builder = TestMultipartBuilder::new()
+            .with_timestamp_field_at("sent_at", 1_700_000_000_000)
+            .with_text("title", "hello");
+        let fields = parse(builder.clone().build()).await.unwrap();
+        let names: Vec<_> = fields.iter().map(|field| field.name.as_str()).collect();
+        assert_eq!(names, ["title", "sent_at"]);
+
+        let (content_type, body) = builder.build();
+        let req = test::TestRequest::post()
+            .uri("/latency")
+            .insert_header(content_type)
+            .set_payload(body)
+            .to_request();
+        let resp = test::call_and_read_body(&app, req).await;
+        assert_eq!(resp, "250");
+
+        let millis = || {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_millis()
+        };
+        let builder = TestMultipartBuilder::new()
+            .with_timestamp_field("sent_at")
+            .with_text("title", "hello");
+        let before = millis();
+        let fields = parse(builder.build()).await.unwrap();
+        let after = millis();
+
+        assert_eq!(fields[1].name, "sent_at");
+        let sent_at: u128 = std::str::from_utf8(&fields[1].content)
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((before..=after).contains(&sent_at));
+    }
+
+    /// Resolves a file's type by sniffing first, then the extension, then the declared type.
+    #[post("/resolve-type")]
+    async fn resolve_type(mut payload: Multipart) -> HttpResponse {
+        let Some(Ok(mut field)) = payload.next().await else {
+            return HttpResponse::BadRequest().finish();
+        };
+        let declared = field
+            .content_type()
+            .map(|mime| mime.essence_str().to_string());
+        let extension = field
+            .content_disposition()
+            .and_then(|disposition| disposition.get_filename())
+            .and_then(|filename| filename.rsplit_once('.'))
+            .map(|(_, extension)| extension.to_string());
+        let content = field.bytes(usize::MAX).await.unwrap().unwrap();
+        let sniffed = infer::get(&content).map(|kind| kind.mime_type().to_string());
+
+        let resolved = sniffed
+            .clone()
+            .or_else(|| {
+                extension
+                    .as_deref()
+                    .map(|extension| format!("image/{extension}"))
+            })
+            .or(declared.clone())
+            .unwrap_or_default();
+        HttpResponse::Ok().body(format!(
+            "{resolved} (sniffed {sniffed:?}, extension {extension:?}, declared {declared:?})"
+        ))
+    }
+
+    #[actix_web::test]
+    async fn test_mismatched_file() {
+        let app = test::init_service(App::new().service(resolve_type)).await;
+        let jpeg = b"\xff\xd8\xff\xe0\x00\x10JFIF\x00";
+
+        let builder = TestMultipartBuilder::new().with_mismatched_file(
+            "photo",
+            "photo.png",
+            "text/plain",
+            &jpeg[..],
+        );
+        let (content_type, body) = builder.build();
+        let req = test::TestRequest::post()
+            .uri("/resolve-type")
+            .insert_header(content_type)
+            .set_payload(body)
+            .to_request();
+        let resp = test::call_and_read_body(&app, req).await;
+
+        assert_eq!(
+            resp,
+            "image/jpeg (sniffed Some(\"image/jpeg\"), extension Some(\"png\"), \
+             declared Some(\"text/plain\"))"
+        );
+    }
+
+    #[cfg(feature = "async-std")]
+    #[actix_web::test]
+    async fn test_into_async_std_body() {
+        let builder = TestMultipartBuilder::new()
+            .with_text("title", "hello")
+            .with_bytes(
+                "file",
+                "a.bin",
+                "application/octet-stream",
+                &b"\x00\x01"[..],
+            );
+
+        let (content_type, body) = builder.clone().into_async_std_body();
+        let ((_, expected_content_type), expected_body) = builder.build();
+
+        assert_eq!(content_type, expected_content_type.to_str().unwrap());
+        assert_eq!(body, expected_body);
+    }
+
+    #[actix_web::test]
+    async fn test_empty_content_type_part() {
+        let app = test::init_service(App::new().service(echo_content_types)).await;
+        let builder = TestMultipartBuilder::new().with_empty_content_type_part("blob", "data");
+        let (content_type, body) = builder.build();
+        assert!(String::from_utf8_lossy(&body).contains("Content-Type: \r\n"));
+
+        let req = test::TestRequest::post()
+            .uri("/content-types")
+            .insert_header(content_type)
+            .set_payload(body)
+            .to_request();
+        let resp = test::call_and_read_body(&app, req).await;
+
+        // actix accepts the part but reports no content type, so the handler falls back
+        assert_eq!(resp, "blob: application/octet-stream");
+    }
 
+    #[actix_web::test]
+    async fn test_build_with_expectation() {
+        let builder = TestMultipartBuilder::new()
+            .with_text("title", "hello")
+            .with_bytes(
+                "file",
+                "a.bin",
+                "application/octet-stream",
+                &b"\x00\x01"[..],
+            )
+            .with_file_no_content_type("raw", "raw.dat", "raw")
+            .with_empty_file("empty", "empty.txt", "text/plain");
 
-    #[derive(Debug, Deserialize, Serialize)]
-    struct Metadata {
-        name: String,
+        let (payload, expected) = builder.build_with_expectation();
+        let actual: Vec<_> = parse(payload)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|field| ExpectedPart {
+                name: field.name,
+                content_type: field.content_type,
+                filename: field.filename,
+                content: field.content,
+            })
+            .collect();
+
+        assert_eq!(expected.len(), 4);
+        assert_eq!(actual, expected);
     }
 
-    #[derive(Debug, MultipartForm)]
-    struct UploadForm {
-        // Note: the form is also subject to the global limits configured using `MultipartFormConfig`.
-        #[multipart(limit = "100MB")]
-        file: TempFile,
-        json: MpJson<Metadata>,
+    #[cfg(feature = "futures")]
+    #[actix_web::test]
+    async fn test_async_read() {
+        let reader = futures_util::io::Cursor::new(b"streamed content".to_vec());
+        let builder = TestMultipartBuilder::new()
+            .with_async_read("file", "stream.txt", "text/plain", reader)
+            .await
+            .unwrap();
+
+        let fields = parse(builder.build()).await.unwrap();
+
+        assert_eq!(fields[0].filename.as_deref(), Some("stream.txt"));
+        assert_eq!(fields[0].content, "streamed content");
     }
 
-    #[post("/videos")]
-    async fn post_video(MultipartForm(form): MultipartForm<UploadForm>) -> impl Responder {
-        format!(
-            "Uploaded file {}, with size: {}\ntemporary file ({}) was deleted\n",
-            form.json.name,
-            form.file.size,
-            form.file.file.path().display(),
-        )
+    #[actix_web::test]
+    async fn test_folded_header() {
+        let builder = TestMultipartBuilder::new()
+            .with_text("title", "hello")
+            .with_folded_header("X-Comment", "first line", "second line");
+        let (content_type, body) = builder.build();
+        assert!(
+            String::from_utf8_lossy(&body).contains("X-Comment: first line\r\n\t second line\r\n")
+        );
+
+        let result = parse((content_type, body)).await;
+
+        assert!(matches!(result, Err(MultipartError::Parse(_))));
     }
 
     #[actix_web::test]
-    async fn test_builder_against_handler() {
-        // Arrange
-        let app = test::init_service(
-            App::new()
-                .service(post_video)
-                .app_data(MultipartFormConfig::default().total_limit(100 * 1024 * 1024)),
-        )
-        .await;
+    async fn test_take_parts() {
+        let builder = TestMultipartBuilder::new()
+            .with_text("a", "1")
+            .with_text("b", "2")
+            .with_bytes("c", "c.bin", "application/octet-stream", &b"3"[..])
+            .with_text("d", "4");
 
-        let metadata = Metadata {
-            name: "MyTestVideo".to_string(),
+        let fields = parse(builder.take_parts(2).build()).await.unwrap();
+        let names: Vec<_> = fields.iter().map(|field| field.name.as_str()).collect();
+
+        assert_eq!(names, ["a", "b"]);
+        assert_eq!(fields[1].content, "2");
+    }
+
+    #[actix_web::test]
+    async fn test_regex_unsafe_boundary() {
+        let builder = TestMultipartBuilder::new().with_regex_unsafe_boundary();
+        let ((_, content_type), _) = builder.clone().build();
+        let boundary = content_type
+            .to_str()
+            .unwrap()
+            .split_once("boundary=")
+            .unwrap()
+            .1
+            .trim_matches('"')
+            .to_string();
+        assert!(super::is_valid_boundary(&boundary));
+        assert!(boundary.contains(".+"));
+
+        // Matches the boundary as a regex, but not literally
+        let decoy = format!("--{}", boundary.replace(".+?(x)", "ANYTHINGx"));
+        let fields = parse(builder.with_text("a", &decoy).with_text("b", "2").build())
+            .await
+            .unwrap();
+
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[0].content, decoy);
+    }
+
+    #[cfg(feature = "unicode")]
+    #[actix_web::test]
+    async fn test_filename_normalized() {
+        use super::NormalizationForm;
+
+        let (_, nfc) = TestMultipartBuilder::new()
+            .with_filename_normalized(
+                "file",
+                "cafe\u{301}.txt",
+                NormalizationForm::Nfc,
+                "text/plain",
+                "x",
+            )
+            .build();
+        let (_, nfd) = TestMultipartBuilder::new()
+            .with_filename_normalized(
+                "file",
+                "caf\u{e9}.txt",
+                NormalizationForm::Nfd,
+                "text/plain",
+                "x",
+            )
+            .build();
+
+        let contains = |body: &Bytes, needle: &str| {
+            body.windows(needle.len())
+                .any(|window| window == needle.as_bytes())
         };
-        let file_content = "This is a dummy video file".as_bytes();
+        assert!(contains(&nfc, "filename=\"caf\u{e9}.txt\""));
+        assert!(!contains(&nfc, "e\u{301}"));
+        assert!(contains(&nfd, "filename=\"cafe\u{301}.txt\""));
+        assert!(!contains(&nfd, "\u{e9}"));
+    }
 
+    #[actix_web::test]
+    async fn test_content_length_header() {
         let builder = TestMultipartBuilder::new()
-            .with_json("json", &metadata)
+            .with_text("title", "hello")
+            .with_bytes("file", "a.bin", "application/octet-stream", &b"12345"[..]);
+
+        let (name, value) = builder.content_length_header();
+        let (content_type, body) = builder.clone().build();
+
+        assert_eq!(name, CONTENT_LENGTH);
+        assert_eq!(
+            value.to_str().unwrap().parse::<usize>().unwrap(),
+            body.len()
+        );
+        assert_eq!(builder.content_type_header(), content_type);
+    }
+
+    #[actix_web::test]
+    async fn test_content_type_header_with_timestamp_field() {
+        // The header and the body may be taken at different times, so different timestamps
+        // must not change the boundary
+        let builder = |millis| {
+            TestMultipartBuilder::new()
+                .with_text("title", "hello")
+                .with_timestamp_field_at("sent_at", millis)
+                .with_content_derived_boundary()
+        };
+
+        let content_type = builder(1_700_000_000_000).content_type_header();
+        let (built_content_type, _) = builder(1_700_000_000_001).build();
+
+        assert_eq!(content_type, built_content_type);
+    }
+
+    #[actix_web::test]
+    async fn test_disposition_escaping() {
+        let filename = "my \"final\" report (v2) \u{e9}t\u{e9}.pdf";
+        let builder = TestMultipartBuilder::new()
+            .with_text("plain", "simple")
+            .with_bytes("file \"\u{fc}\"", filename, "application/pdf", &b"%PDF"[..]);
+        let (content_type, body) = builder.build();
+        let raw = String::from_utf8_lossy(&body);
+
+        assert!(raw.contains("Content-Disposition: form-data; name=\"plain\"\r\n"));
+        assert!(raw.contains(
+            "filename=\"my \\\"final\\\" report (v2) \u{e9}t\u{e9}.pdf\"; \
+             filename*=UTF-8''my%20%22final%22%20report%20%28v2%29%20%C3%A9t%C3%A9.pdf\r\n"
+        ));
+
+        let fields = parse((content_type, body)).await.unwrap();
+
+        assert_eq!(fields[0].name, "plain");
+        assert_eq!(fields[1].name, "file \"\u{fc}\"");
+        assert_eq!(fields[1].filename.as_deref(), Some(filename));
+        assert_eq!(fields[1].content, "%PDF");
+    }
+
+    #[actix_web::test]
+    async fn test_disposition_header_injection() {
+        let builder = TestMultipartBuilder::new().with_bytes(
+            "file",
+            "evil.txt\"\r\nContent-Type: text/html\r\nX: \"",
+            "text/plain",
+            "data",
+        );
+        let (content_type, body) = builder.build();
+        let raw = String::from_utf8_lossy(&body);
+
+        assert!(
+            raw.contains("filename=\"evil.txt\\\"%0D%0AContent-Type: text/html%0D%0AX: \\\"\"\r\n")
+        );
+
+        let fields = parse((content_type, body)).await.unwrap();
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0].content_type.as_deref(), Some("text/plain"));
+    }
+
+    #[actix_web::test]
+    async fn test_crlf_heavy_content() {
+        let builder = TestMultipartBuilder::new()
+            .with_crlf_heavy_content("blob")
+            .with_text("after", "tail");
+
+        let fields = parse(builder.build()).await.unwrap();
+
+        assert_eq!(
+            fields[0].content,
+            &b"\r\n--\r\n---\r\n----\r\n--\r\n\r\n--x\r\n--\0\r\n\r\n--\r\n"[..]
+        );
+        assert_eq!(fields[1].content, "tail");
+    }
+
+    #[post("/raw-length")]
+    async fn raw_length(body: web::Bytes) -> HttpResponse {
+        HttpResponse::Ok().body(body.len().to_string())
+    }
+
+    #[actix_web::test]
+    async fn test_into_raw_payload() {
+        let app = test::init_service(App::new().service(raw_length)).await;
+        let builder = TestMultipartBuilder::new()
+            .with_text("title", "hello")
+            .with_bytes("file", "a.bin", "application/octet-stream", &b"12345"[..]);
+        let expected_len = builder.byte_len();
+
+        let (content_type, body) = builder.into_raw_payload();
+        let req = test::TestRequest::post()
+            .uri("/raw-length")
+            .insert_header((CONTENT_TYPE, content_type))
+            .set_payload(body)
+            .to_request();
+        let resp = test::call_and_read_body(&app, req).await;
+
+        assert_eq!(resp, expected_len.to_string());
+    }
+
+    /// Echoes `name: content type, transfer encoding` per field, using `-` for missing headers.
+    #[post("/part-headers")]
+    async fn echo_part_headers(mut payload: Multipart) -> HttpResponse {
+        let mut lines = Vec::new();
+        while let Some(Ok(field)) = payload.next().await {
+            let header = |name: &str| {
+                field
+                    .headers()
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+                    .unwrap_or("-")
+                    .to_string()
+            };
+            lines.push(format!(
+                "{}: {}, {}",
+                field.name().unwrap_or_default(),
+                header("content-type"),
+                header("content-transfer-encoding")
+            ));
+        }
+        HttpResponse::Ok().body(lines.join("\n"))
+    }
+
+    #[actix_web::test]
+    async fn test_part_headers() {
+        let app = test::init_service(App::new().service(echo_part_headers)).await;
+        let builder = TestMultipartBuilder::new()
+            .with_field("comment", "hello")
             .with_bytes(
                 "file",
-                "test_video.mp4",
-                "video/mp4",
-                file_content,
+                "a.bin",
+                "application/octet-stream",
+                &b"\x00\x01"[..],
+            )
+            .with_part_header("Content-Transfer-Encoding", "binary")
+            .with_part_header("X-Trace", "1")
+            .with_text("title", "plain");
+        let (content_type, body) = builder.build();
+        let raw = String::from_utf8_lossy(&body);
+        assert!(raw.contains("name=\"comment\"\r\n\r\nhello\r\n"));
+        assert!(raw.contains(
+            "Content-Type: application/octet-stream\r\n\
+             Content-Transfer-Encoding: binary\r\nX-Trace: 1\r\n\r\n"
+        ));
+
+        let req = test::TestRequest::post()
+            .uri("/part-headers")
+            .insert_header(content_type)
+            .set_payload(body)
+            .to_request();
+        let resp = test::call_and_read_body(&app, req).await;
+
+        assert_eq!(
+            resp,
+            "comment: -, -\nfile: application/octet-stream, binary\ntitle: text/plain, -"
+        );
+    }
+
+    #[post("/form")]
+    async fn echo_form(form: web::Form<Vec<(String, String)>>) -> HttpResponse {
+        let pairs: Vec<_> = form
+            .into_inner()
+            .into_iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect();
+        HttpResponse::Ok().body(pairs.join("\n"))
+    }
+
+    #[actix_web::test]
+    async fn test_build_urlencoded_escaping() {
+        let app = test::init_service(App::new().service(echo_form)).await;
+        let builder = TestMultipartBuilder::new()
+            .with_text("tricky", "a=b&c+d%e")
+            .with_text("a b&c", "caf\u{e9} ok");
+
+        let (content_type, body) = builder.build_urlencoded();
+        assert_eq!(body, "tricky=a%3Db%26c%2Bd%25e&a+b%26c=caf%C3%A9+ok");
+
+        let req = test::TestRequest::post()
+            .uri("/form")
+            .insert_header(content_type)
+            .set_payload(body)
+            .to_request();
+        let resp = test::call_and_read_body(&app, req).await;
+
+        assert_eq!(resp, "tricky=a=b&c+d%e\na b&c=caf\u{e9} ok");
+    }
+
+    #[actix_web::test]
+    async fn test_leading_bom() {
+        let builder = TestMultipartBuilder::new()
+            .with_leading_bom()
+            .with_text("title", "hello")
+            .with_text("body", "world");
+        let (content_type, body) = builder.build();
+        assert!(body.starts_with(b"\xef\xbb\xbf--"));
+
+        // actix skips the BOM-prefixed first delimiter line as preamble, silently losing the
+        // first part instead of rejecting the body
+        let fields = parse((content_type, body)).await.unwrap();
+        let names: Vec<_> = fields.iter().map(|field| field.name.as_str()).collect();
+
+        assert_eq!(names, ["body"]);
+        assert_eq!(fields[0].content, "world");
+    }
+
+    /// Collects the values of all `a` fields in encounter order.
+    #[post("/collect-a")]
+    async fn collect_a(mut payload: Multipart) -> HttpResponse {
+        let mut values = Vec::new();
+        while let Some(Ok(mut field)) = payload.next().await {
+            let is_a = field.name() == Some("a");
+            let content = field.bytes(usize::MAX).await.unwrap().unwrap();
+            if is_a {
+                values.push(String::from_utf8_lossy(&content).into_owned());
+            }
+        }
+        HttpResponse::Ok().body(values.join(","))
+    }
+
+    #[actix_web::test]
+    async fn test_interleaved() {
+        let app = test::init_service(App::new().service(collect_a)).await;
+        let builder = TestMultipartBuilder::new().with_interleaved(&[
+            ("a", "1"),
+            ("b", "x"),
+            ("a", "2"),
+            ("b", "y"),
+            ("a", "3"),
+        ]);
+        let (content_type, body) = builder.build();
+
+        let req = test::TestRequest::post()
+            .uri("/collect-a")
+            .insert_header(content_type)
+            .set_payload(body)
+            .to_request();
+        let resp = test::call_and_read_body(&app, req).await;
+
+        assert_eq!(resp, "1,2,3");
+    }
+
+    /// Accepts at most `MAX_FIELDS` fields, rejecting anything more with 413.
+    #[post("/capped")]
+    async fn capped_fields(mut payload: Multipart) -> HttpResponse {
+        const MAX_FIELDS: usize = 1000;
+        let mut count = 0;
+        while let Some(Ok(_)) = payload.next().await {
+            count += 1;
+            if count > MAX_FIELDS {
+                return HttpResponse::PayloadTooLarge().finish();
+            }
+        }
+        HttpResponse::Ok().body(count.to_string())
+    }
+
+    #[actix_web::test]
+    async fn test_max_parts() {
+        let builder = TestMultipartBuilder::new().with_max_parts("field", 1000);
+        let fields = parse(builder.clone().build()).await.unwrap();
+        assert_eq!(fields.len(), 1000);
+        assert_eq!(fields[0].name, "field_0");
+        assert_eq!(fields[999].name, "field_999");
+        assert_eq!(fields[999].content, "999");
+
+        let app = test::init_service(App::new().service(capped_fields)).await;
+        for (builder, expected) in [
+            (builder.clone(), StatusCode::OK),
+            (
+                builder.with_text("extra", "1"),
+                StatusCode::PAYLOAD_TOO_LARGE,
+            ),
+        ] {
+            let (content_type, body) = builder.build();
+            let req = test::TestRequest::post()
+                .uri("/capped")
+                .insert_header(content_type)
+                .set_payload(body)
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+
+            assert_eq!(resp.status(), expected);
+        }
+    }
+
+    /// Decodes the first field per its declared charset, replacing invalid UTF-8 sequences.
+    #[post("/decode")]
+    async fn decode_text(mut payload: Multipart) -> HttpResponse {
+        let Some(Ok(mut field)) = payload.next().await else {
+            return HttpResponse::BadRequest().finish();
+        };
+        let charset = field
+            .content_type()
+            .and_then(|mime| mime.get_param("charset"))
+            .map(|charset| charset.as_str().to_ascii_lowercase());
+        let content = field.bytes(usize::MAX).await.unwrap().unwrap();
+        let text = match charset.as_deref() {
+            Some("iso-8859-1") => content.iter().map(|&byte| byte as char).collect(),
+            _ => String::from_utf8_lossy(&content).into_owned(),
+        };
+        HttpResponse::Ok().body(text)
+    }
+
+    #[actix_web::test]
+    async fn test_charset_mismatch() {
+        let app = test::init_service(App::new().service(decode_text)).await;
+        for (charset, bytes, expected) in [
+            ("utf-8", &b"caf\xe9"[..], "caf\u{fffd}"),
+            ("iso-8859-1", "caf\u{e9}".as_bytes(), "caf\u{c3}\u{a9}"),
+        ] {
+            let builder = TestMultipartBuilder::new().with_charset_mismatch("text", charset, bytes);
+            let (content_type, body) = builder.build();
+            assert!(
+                String::from_utf8_lossy(&body)
+                    .contains(&format!("Content-Type: text/plain; charset={charset}\r\n"))
             );
 
+            let req = test::TestRequest::post()
+                .uri("/decode")
+                .insert_header(content_type)
+                .set_payload(body)
+                .to_request();
+            let resp = test::call_and_read_body(&app, req).await;
+
+            assert_eq!(resp, expected);
+        }
+    }
+
+    #[cfg(feature = "sha2")]
+    #[actix_web::test]
+    async fn test_build_with_digest() {
+        use sha2::{Digest, Sha256};
+
+        let builder = TestMultipartBuilder::new()
+            .with_text("title", "hello")
+            .with_bytes("file", "a.bin", "application/octet-stream", &b"12345"[..]);
+
+        let ((_, body), digest) = builder.build_with_digest();
+        let mut hasher = Sha256::new();
+        hasher.update(&body);
+
+        assert_eq!(digest, <[u8; 32]>::from(hasher.finalize()));
+    }
+
+    /// Routes file fields by their custom `category` disposition parameter.
+    #[post("/categorize")]
+    async fn categorize(mut payload: Multipart) -> HttpResponse {
+        let mut lines = Vec::new();
+        while let Some(Ok(field)) = payload.next().await {
+            let category = field
+                .content_disposition()
+                .and_then(|disposition| disposition.get_unknown("category"))
+                .unwrap_or_default();
+            let destination = match category {
+                "avatar" => "avatars",
+                "document" => "documents",
+                _ => "misc",
+            };
+            lines.push(format!(
+                "{} -> {}",
+                field.name().unwrap_or_default(),
+                destination
+            ));
+        }
+        HttpResponse::Ok().body(lines.join("\n"))
+    }
+
+    #[actix_web::test]
+    async fn test_disposition_param() {
+        let app = test::init_service(App::new().service(categorize)).await;
+        let builder = TestMultipartBuilder::new()
+            .with_bytes("face", "me.png", "image/png", &b"png"[..])
+            .with_disposition_param("category", "avatar")
+            .with_bytes("cv", "cv.pdf", "application/pdf", &b"pdf"[..])
+            .with_disposition_param("category", "document")
+            .with_text("note", "hi");
         let (content_type, body) = builder.build();
+        assert!(String::from_utf8_lossy(&body).contains(
+            "Content-Disposition: form-data; name=\"face\"; filename=\"me.png\"; category=\"avatar\"\r\n"
+        ));
 
-        // Act
         let req = test::TestRequest::post()
-            .uri("/videos")
+            .uri("/categorize")
             .insert_header(content_type)
             .set_payload(body)
             .to_request();
+        let resp = test::call_and_read_body(&app, req).await;
 
-        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp, "face -> avatars\ncv -> documents\nnote -> misc");
+    }
 
-        // Assert
-        assert!(resp.status().is_success(), "Response was not 2xx");
+    #[actix_web::test]
+    #[should_panic(expected = "invalid disposition parameter name")]
+    async fn test_disposition_param_rejects_injection() {
+        TestMultipartBuilder::new()
+            .with_text("note", "hi")
+            .with_disposition_param("k\r\nX-Injected: 1\r\nfoo", "bar");
+    }
 
-        let body_bytes = test::read_body(resp).await;
-        let body_str = String::from_utf8(body_bytes.to_vec()).unwrap();
+    #[actix_web::test]
+    async fn test_long_header() {
+        let builder = TestMultipartBuilder::new()
+            .with_text("title", "hello")
+            .with_long_header("X-Padding", 1024 * 1024)
+            .with_text("after", "tail");
+        let (_, body) = builder.clone().build();
+        assert!(
+            body.windows(1024 * 1024)
+                .any(|window| window.iter().all(|&b| b == b'x'))
+        );
 
-        assert!(body_str.contains("MyTestVideo"));
-        assert!(body_str.contains(&file_content.len().to_string()));
-        assert!(body_str.contains("was deleted"));
+        // actix_multipart has no header length limit, so even a 1 MiB value is accepted
+        for _ in 0..2 {
+            let fields = parse(builder.clone().build()).await.unwrap();
+            assert_eq!(fields.len(), 2);
+            assert_eq!(fields[0].content, "hello");
+            assert_eq!(fields[1].content, "tail");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_snapshot() {
+        let path = std::env::temp_dir().join(format!("{}.snap", uuid::Uuid::new_v4()));
+        let builder = TestMultipartBuilder::new()
+            .with_text("title", "hello")
+            .with_bytes("file", "a.bin", "application/octet-stream", &b"12345"[..]);
+
+        builder.assert_matches_snapshot(&path);
+        let written = std::fs::read(&path).unwrap();
+        TestMultipartBuilder::new()
+            .with_text("title", "hello")
+            .with_bytes("file", "a.bin", "application/octet-stream", &b"12345"[..])
+            .assert_matches_snapshot(&path);
+
+        let changed = builder.with_text("extra", "1");
+        let result = std::panic::catch_unwind(|| changed.assert_matches_snapshot(&path));
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(String::from_utf8_lossy(&written).contains("name=\"title\""));
+        let message = result.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.starts_with("body does not match snapshot"));
+        assert!(message.contains("+ Content-Disposition: form-data; name=\"extra\"\n"));
+    }
+
+    #[actix_web::test]
+    async fn test_lowercase_transfer_encoding() {
+        let builder = TestMultipartBuilder::new()
+            .with_header_case(HeaderCase::Uppercase)
+            .with_bytes("file", "a.bin", "application/octet-stream", "aGVsbG8=")
+            .with_part_header("content-transfer-encoding", "base64");
+        let (content_type, body) = builder.build();
+        assert!(
+            String::from_utf8_lossy(&body).contains("\r\ncontent-transfer-encoding: base64\r\n")
+        );
+
+        let fields = parse((content_type, body)).await.unwrap();
+
+        // Known limitation: actix ignores Content-Transfer-Encoding in any casing, so the
+        // content arrives still base64-encoded
+        assert_eq!(fields[0].content, "aGVsbG8=");
+    }
+
+    #[actix_web::test]
+    async fn test_without_interpart_crlf() {
+        let builder = TestMultipartBuilder::new()
+            .with_text("title", "hello")
+            .with_text("body", "world");
+        let compliant = parse(builder.clone().build()).await.unwrap();
+        assert_eq!(compliant.len(), 2);
+
+        let (content_type, body) = builder.without_interpart_crlf().build();
+        assert!(String::from_utf8_lossy(&body).contains("\r\n\r\nhello--"));
+
+        // actix never finds a delimiter and keeps waiting for more data instead of erroring
+        let result = timeout(Duration::from_secs(1), parse((content_type, body))).await;
+        assert!(result.is_err());
+
+        // Content already ending in CRLF keeps the delimiter CRLF and arrives intact
+        let terminated = TestMultipartBuilder::new()
+            .with_text("title", "hello\r\n")
+            .with_text("body", "world\r\n")
+            .without_interpart_crlf();
+        let fields = parse(terminated.build()).await.unwrap();
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[0].content, "hello\r\n");
+        assert_eq!(fields[1].content, "world\r\n");
+    }
+
+    #[actix_web::test]
+    async fn test_max_length_boundary() {
+        let builder = TestMultipartBuilder::new()
+            .with_max_length_boundary()
+            .with_text("title", "hello")
+            .with_bytes("file", "a.bin", "application/octet-stream", &b"12345"[..]);
+        let (content_type, body) = builder.build();
+        let boundary = content_type
+            .1
+            .to_str()
+            .unwrap()
+            .split_once("boundary=")
+            .unwrap()
+            .1
+            .trim_matches('"')
+            .to_string();
+
+        assert_eq!(boundary.len(), 70);
+        assert!(super::is_valid_boundary(&boundary));
+        assert!(body.starts_with(format!("--{boundary}\r\n").as_bytes()));
+        assert!(body.ends_with(format!("\r\n--{boundary}--\r\n").as_bytes()));
+
+        let fields = parse((content_type, body)).await.unwrap();
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[1].content, "12345");
+    }
+
+    #[actix_web::test]
+    async fn test_smuggled_trailing_part() {
+        let builder = TestMultipartBuilder::new()
+            .with_text("title", "hello")
+            .with_smuggled_trailing_part("role", "admin");
+        let (content_type, body) = builder.build();
+        let raw = String::from_utf8_lossy(&body);
+        let closing = raw.find("--\r\n").unwrap();
+        assert!(
+            raw[closing..].contains("name=\"role\"\r\nContent-Type: text/plain\r\n\r\nadmin\r\n")
+        );
+
+        let fields = parse((content_type, body)).await.unwrap();
+        let names: Vec<_> = fields.iter().map(|field| field.name.as_str()).collect();
+
+        assert_eq!(names, ["title"]);
+    }
+
+    #[actix_web::test]
+    async fn test_single_quoted_disposition() {
+        let builder = TestMultipartBuilder::new()
+            .with_single_quoted_disposition("file", "quoted")
+            .with_text("title", "hello");
+        let (content_type, body) = builder.build();
+        assert!(
+            String::from_utf8_lossy(&body)
+                .contains("Content-Disposition: form-data; name='file'\r\n")
+        );
+
+        let app = test::init_service(App::new().service(echo_content_types)).await;
+        let req = test::TestRequest::post()
+            .uri("/content-types")
+            .insert_header(content_type)
+            .set_payload(body)
+            .to_request();
+        let resp = test::call_and_read_body(&app, req).await;
+
+        // actix reads the single-quoted value as a bare token, keeping the quotes in the name
+        assert_eq!(resp, "'file': text/plain\ntitle: text/plain");
+    }
+
+    #[actix_web::test]
+    async fn test_timestamp_field_matches_content_derived_boundary() {
+        let ((_, content_type), body) = TestMultipartBuilder::new()
+            .with_text("title", "hello")
+            .with_timestamp_field("sent_at")
+            .with_content_derived_boundary()
+            .build();
+        let boundary = content_type
+            .to_str()
+            .unwrap()
+            .split_once("boundary=")
+            .unwrap()
+            .1
+            .to_string();
+
+        assert!(body.starts_with(format!("--{boundary}\r\n").as_bytes()));
+    }
+
+    #[actix_web::test]
+    async fn test_build_per_part_drops_smuggled_parts() {
+        let payloads = TestMultipartBuilder::new()
+            .with_text("title", "hello")
+            .with_text("body", "world")
+            .with_smuggled_trailing_part("role", "admin")
+            .build_per_part();
+
+        assert_eq!(payloads.len(), 2);
+        for (_, body) in payloads {
+            assert!(!String::from_utf8_lossy(&body).contains("name=\"role\""));
+        }
     }
-}
\ No newline at end of file
+}